@@ -397,6 +397,10 @@ fn text(f: &mut Context, v: &Text) -> Result<()> {
 					TextSegment::Byte(n) => {
 						write!(f, "{{0x{n:02X}}}")?
 					}
+					TextSegment::Face(_) | TextSegment::Position(_) | TextSegment::Voice(_) | TextSegment::Command(..) => {
+						let (c, n) = next.command().unwrap();
+						write!(f, "#{n}{c}")?
+					}
 				}
 			})
 		}).strict()?;
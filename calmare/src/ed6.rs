@@ -21,6 +21,7 @@ pub fn write(mut f: Context, scena: &ed6::Scena) -> Result<()> {
 		look_points,
 		entries,
 		functions,
+		layout: _, // not written, so calmare loses nonstandard layouts
 	} = scena;
 
 	f.kw("scena")?.kw("ed6")?.suf(":")?.line()?.indent(|f| {
@@ -1,11 +1,23 @@
 mod enc;
 mod dec;
+mod remap;
+
+pub use remap::Remap;
 
 pub fn decode(bytes: &[u8]) -> Result<String, usize> {
+	decode_with(bytes, &Remap::new())
+}
+
+pub fn decode_lossy(bytes: &[u8]) -> String {
+	decode_lossy_with(bytes, &Remap::new())
+}
+
+/// Like [`decode`], but with the mappings in `remap` taking priority over the standard ones.
+pub fn decode_with(bytes: &[u8], remap: &Remap) -> Result<String, usize> {
 	let mut out = String::with_capacity(bytes.len());
 	let mut pos = 0;
 	while pos < bytes.len() {
-		let (ch, len) = decode_char(&bytes[pos..]);
+		let (ch, len) = decode_char_with(&bytes[pos..], remap);
 		let ch = ch.ok_or(pos)?;
 		out.push(ch);
 		pos += len;
@@ -13,11 +25,11 @@ pub fn decode(bytes: &[u8]) -> Result<String, usize> {
 	Ok(out)
 }
 
-pub fn decode_lossy(bytes: &[u8]) -> String {
+pub fn decode_lossy_with(bytes: &[u8], remap: &Remap) -> String {
 	let mut out = String::with_capacity(bytes.len());
 	let mut pos = 0;
 	while pos < bytes.len() {
-		let (ch, len) = decode_char(&bytes[pos..]);
+		let (ch, len) = decode_char_with(&bytes[pos..], remap);
 		let ch = ch.unwrap_or('�');
 		out.push(ch);
 		pos += len;
@@ -25,6 +37,13 @@ pub fn decode_lossy(bytes: &[u8]) -> String {
 	out
 }
 
+fn decode_char_with(bytes: &[u8], remap: &Remap) -> (Option<char>, usize) {
+	match remap.decode_char(bytes) {
+		Some((ch, len)) => (Some(ch), len),
+		None => decode_char(bytes),
+	}
+}
+
 fn decode_char(bytes: &[u8]) -> (Option<char>, usize) {
 	use std::char::from_u32 as ch;
 	let c = match bytes.first() {
@@ -69,8 +88,16 @@ fn decode_char(bytes: &[u8]) -> (Option<char>, usize) {
 }
 
 pub fn encode(text: &str) -> Result<Vec<u8>, usize> {
+	encode_with(text, &Remap::new())
+}
+
+/// Like [`encode`], but with the mappings in `remap` taking priority over the standard ones.
+pub fn encode_with(text: &str, remap: &Remap) -> Result<Vec<u8>, usize> {
 	let mut out = Vec::new();
 	for (pos, ch) in text.char_indices() {
+		if remap.encode_char(ch, &mut out) {
+			continue
+		}
 		let ch = ch as u32;
 		match ch {
 			0x00..=0x80 => out.push(ch as u8),
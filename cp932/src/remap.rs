@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+/// A table of overrides on top of the standard mapping, as used by fan translations that replace
+/// glyphs in the game's font.
+///
+/// Codes are stored as `u16`: a single byte `b` as `b`, and a two-byte sequence `[c1, c2]` as
+/// `c1 << 8 | c2`. Since no valid lead byte is below 0x81, these never overlap.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Remap {
+	decode: BTreeMap<u16, char>,
+	encode: BTreeMap<char, u16>,
+}
+
+impl std::fmt::Debug for Remap {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Remap").field("len", &self.decode.len()).finish()
+	}
+}
+
+impl Remap {
+	pub const fn new() -> Self {
+		Remap {
+			decode: BTreeMap::new(),
+			encode: BTreeMap::new(),
+		}
+	}
+
+	/// Maps `code` to `ch`, in both directions.
+	///
+	/// If either side was already mapped, the old mapping is removed.
+	pub fn insert(&mut self, code: u16, ch: char) {
+		if let Some(old) = self.decode.insert(code, ch) {
+			self.encode.remove(&old);
+		}
+		if let Some(old) = self.encode.insert(ch, code) {
+			if old != code {
+				self.decode.remove(&old);
+			}
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.decode.is_empty()
+	}
+
+	/// Parses a table, with one mapping per line.
+	///
+	/// Each line consists of a hex code (two digits, or four starting with a lead byte of at least
+	/// 0x81), whitespace, and either a single char or a `U+XXXX` code point. Empty lines and lines starting with `#` are ignored.
+	///
+	/// ```text
+	/// # Accented letters in the user-defined area
+	/// F040 é
+	/// F041 U+00E8
+	/// ```
+	///
+	/// On error, returns the (zero-based) number of the invalid line.
+	pub fn parse(text: &str) -> Result<Remap, usize> {
+		let mut remap = Remap::new();
+		for (n, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue
+			}
+			let (code, ch) = line.split_once(char::is_whitespace).ok_or(n)?;
+			let ch = ch.trim_start();
+			let len = code.len();
+			let code = u16::from_str_radix(code, 16).map_err(|_| n)?;
+			match len {
+				2 => {}
+				4 if code >= 0x8100 => {}
+				_ => return Err(n),
+			}
+			let ch = if let Some(hex) = ch.strip_prefix("U+") {
+				u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).ok_or(n)?
+			} else {
+				let mut chars = ch.chars();
+				match (chars.next(), chars.next()) {
+					(Some(ch), None) => ch,
+					_ => return Err(n),
+				}
+			};
+			remap.insert(code, ch);
+		}
+		Ok(remap)
+	}
+
+	/// Looks up the char at the start of `bytes`, returning it and its length in bytes.
+	///
+	/// Two-byte codes are checked before single bytes, so if both `c1` and `[c1, c2]` are mapped,
+	/// the latter wins whenever `c1` is followed by `c2`.
+	pub(crate) fn decode_char(&self, bytes: &[u8]) -> Option<(char, usize)> {
+		if self.decode.is_empty() {
+			return None
+		}
+		if let [c1, c2, ..] = *bytes {
+			if let Some(&ch) = self.decode.get(&((c1 as u16) << 8 | c2 as u16)) {
+				return Some((ch, 2))
+			}
+		}
+		let ch = *self.decode.get(&(*bytes.first()? as u16))?;
+		Some((ch, 1))
+	}
+
+	pub(crate) fn encode_char(&self, ch: char, out: &mut Vec<u8>) -> bool {
+		match self.encode.get(&ch) {
+			Some(&code) if code > 0xFF => out.extend(code.to_be_bytes()),
+			Some(&code) => out.push(code as u8),
+			None => return false,
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		let remap = Remap::parse("# comment\n\nF040 é\n  F041\tU+00E8  \n7E ‾\n").unwrap();
+		assert_eq!(remap.decode_char(&[0xF0, 0x40]), Some(('é', 2)));
+		assert_eq!(remap.decode_char(&[0xF0, 0x41]), Some(('è', 2)));
+		assert_eq!(remap.decode_char(&[0x7E]), Some(('‾', 1)));
+		assert_eq!(remap.decode_char(&[0xF0, 0x42]), None);
+		assert!(Remap::parse("").unwrap().is_empty());
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert_eq!(Remap::parse("F040"), Err(0));
+		assert_eq!(Remap::parse("F040 é\nF04 é"), Err(1));
+		assert_eq!(Remap::parse("F0400 é"), Err(0));
+		assert_eq!(Remap::parse("G040 é"), Err(0));
+		assert_eq!(Remap::parse("0040 é"), Err(0));
+		assert_eq!(Remap::parse("F040 éè"), Err(0));
+		assert_eq!(Remap::parse("F040 U+D800"), Err(0));
+		assert_eq!(Remap::parse("F040 U+XYZ"), Err(0));
+		assert_eq!(Remap::parse("# ok\n\nF040 é\n41"), Err(3));
+	}
+
+	#[test]
+	fn priority() {
+		let mut remap = Remap::new();
+		remap.insert(0x88, 'a');
+		remap.insert(0x889F, 'b');
+		assert_eq!(remap.decode_char(&[0x88, 0x9F]), Some(('b', 2)));
+		assert_eq!(remap.decode_char(&[0x88, 0xA0]), Some(('a', 1)));
+		assert_eq!(remap.decode_char(&[0x88]), Some(('a', 1)));
+
+		// Remapped codes take priority over the standard ones
+		assert_eq!(crate::decode(&[0x88, 0x9F]).unwrap(), "亜");
+		assert_eq!(crate::decode_with(&[0x88, 0x9F, 0x41], &remap).unwrap(), "bA");
+		assert_eq!(crate::encode_with("b", &remap).unwrap(), [0x88, 0x9F]);
+		assert_eq!(crate::encode_with("a", &remap).unwrap(), [0x88]);
+	}
+
+	#[test]
+	fn insert_replaces() {
+		let mut remap = Remap::new();
+		remap.insert(0xF040, 'é');
+		remap.insert(0xF040, 'è');
+		assert!(!remap.encode_char('é', &mut Vec::new()));
+		remap.insert(0xF041, 'è');
+		assert_eq!(remap.decode_char(&[0xF0, 0x40]), None);
+		assert_eq!(remap.decode_char(&[0xF0, 0x41]), Some(('è', 2)));
+	}
+
+	#[test]
+	fn roundtrip() {
+		let remap = Remap::parse("F040 é\nF041 è\n5C ¥").unwrap();
+		let text = "café crème ¥100 亜";
+		assert!(crate::encode(text).is_err());
+		let bytes = crate::encode_with(text, &remap).unwrap();
+		assert_eq!(&bytes[..5], [b'c', b'a', b'f', 0xF0, 0x40]);
+		assert_eq!(crate::decode_with(&bytes, &remap).unwrap(), text);
+		assert_eq!(crate::decode_lossy_with(&bytes, &remap), text);
+	}
+
+	#[test]
+	fn errors() {
+		let remap = Remap::parse("F040 é").unwrap();
+		assert_eq!(crate::encode_with("aè", &remap), Err(1));
+		assert_eq!(crate::decode_with(&[b'a', 0xF0, 0x40, 0x85, 0x30], &remap), Err(3));
+		assert_eq!(crate::decode_lossy_with(&[0xF0, 0x40, 0x85, 0x30], &remap), "é�");
+	}
+}
@@ -0,0 +1,18 @@
+use themelios::archive::{Archive, ArchiveBuilder, Archives};
+use crate::util::*;
+
+#[test_case::test_case(&FC; "fc")]
+#[test_case::test_case(&SC; "sc")]
+#[test_case::test_case(&TC; "tc")]
+fn from_bytes(arcs: &Archives) -> Result<(), Error> {
+	for (n, arc) in arcs.archives()? {
+		let (mut dir, mut dat) = (Vec::new(), Vec::new());
+		ArchiveBuilder::from_archive(arc).write(&mut dir, &mut dat).map_err(|e| e.to_string())?;
+		let arc2 = Archive::from_bytes(&dir, &dat[..]).map_err(|e| e.to_string())?;
+		assert_eq!(arc.entries().len(), arc2.entries().len(), "archive {n}");
+		for e in arc.entries() {
+			assert_eq!(arc.get(&e.name), arc2.get(&e.name), "archive {n}: {}", e.name);
+		}
+	}
+	Ok(())
+}
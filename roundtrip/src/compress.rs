@@ -0,0 +1,23 @@
+use themelios::archive::Archives;
+use themelios::{compress::{self, Mode}, decompress};
+use crate::util::*;
+
+#[test_case::test_case(&FC; "fc")]
+#[test_case::test_case(&SC; "sc")]
+#[test_case::test_case(&TC; "tc")]
+fn roundtrip(arcs: &Archives) -> Result<(), Error> {
+	arcs.open_all()?;
+	let mut failed = false;
+	for name in arcs.list() {
+		let Some(data) = arcs.get_decomp(name)? else { continue };
+		for mode in [Mode::Bits, Mode::Bytes] {
+			let data2 = decompress::decompress(&compress::compress(&data, mode, compress::DEFAULT_LEVEL))?;
+			if data != data2 {
+				println!("{name}: differs in {mode:?}");
+				failed = true;
+			}
+		}
+	}
+	assert!(!failed);
+	Ok(())
+}
@@ -3,6 +3,9 @@
 
 mod util;
 mod scena;
+mod compress;
+mod archive;
+mod lookup;
 
 use std::path::Path;
 
@@ -18,7 +21,7 @@ mod todo {
 			#[test_case::test_case(&$crate::util::test::FC; "fc")]
 			fn roundtrip(arc: &$crate::archive::Archives) -> Result<(), $crate::util::test::Error> {
 				$crate::util::test::check_roundtrip_strict(
-					&arc.get_decomp($name).unwrap(),
+					&arc.get_decomp($name)?.unwrap(),
 					super::read,
 					|a| super::write(a),
 				)?;
@@ -34,7 +37,7 @@ mod todo {
 			#[test_case::test_case(&$crate::util::test::FC; "fc")]
 			fn roundtrip(arc: &$crate::archive::Archives) -> Result<(), $crate::util::test::Error> {
 				$crate::util::test::check_roundtrip_strict(
-					&arc.get_decomp($name).unwrap(),
+					&arc.get_decomp($name)?.unwrap(),
 					|a| super::read(arc, a),
 					|a| super::write(arc, a),
 				)?;
@@ -45,7 +48,13 @@ mod todo {
 	pub use __simple_roundtrip_arc as simple_roundtrip_arc;
 
 	mod cook2 {
-		crate::util::test::simple_roundtrip!("t_cook2._dt");
+		use crate::util::test::*;
+
+		#[test_case::test_case(&FC; "fc")]
+		fn roundtrip(arc: &crate::archive::Archives) -> Result<(), Error> {
+			check_roundtrip_strict(&arc.get_decomp("t_cook2._dt")?.unwrap(), |a| super::read(&GD_FC, a), |a| super::write(&GD_FC, a))?;
+			Ok(())
+		}
 	}
 
 	mod item {
@@ -53,11 +62,11 @@ mod todo {
 
 		#[test_case::test_case(&FC; "fc")]
 		fn roundtrip(arc: &crate::archive::Archives) -> Result<(), Error> {
-			let t_item = arc.get_decomp("t_item._dt").unwrap();
-			let t_item2 = arc.get_decomp("t_item2._dt").unwrap();
-			let items = super::read(&t_item, &t_item2)?;
-			let (t_item_, t_item2_) = super::write(&items)?;
-			let items2 = super::read(&t_item_, &t_item2_)?;
+			let t_item = arc.get_decomp("t_item._dt")?.unwrap();
+			let t_item2 = arc.get_decomp("t_item2._dt")?.unwrap();
+			let items = super::read(&GD_FC, &t_item, &t_item2)?;
+			let (t_item_, t_item2_) = super::write(&GD_FC, &items)?;
+			let items2 = super::read(&GD_FC, &t_item_, &t_item2_)?;
 			check_equal(&items, &items2)?;
 			Ok(())
 		}
@@ -76,7 +85,7 @@ mod todo {
 
 		#[test_case::test_case(&FC; "fc")]
 		fn roundtrip(arc: &crate::archive::Archives) -> Result<(), Error> {
-			check_roundtrip(&arc.get_decomp("t_bgmtbl._dt").unwrap(), super::read, super::write)?;
+			check_roundtrip(&arc.get_decomp("t_bgmtbl._dt")?.unwrap(), |a| super::read(&GD_FC, a), |a| super::write(&GD_FC, a))?;
 			Ok(())
 		}
 	}
@@ -98,7 +107,7 @@ mod todo {
 
 		#[test_case::test_case(&FC; "fc")]
 		fn roundtrip(arc: &crate::archive::Archives) -> Result<(), Error> {
-			check_roundtrip(&arc.get_decomp("t_town._dt").unwrap(), super::read, |a| super::write(a))?;
+			check_roundtrip(&arc.get_decomp("t_town._dt")?.unwrap(), |a| super::read(&GD_FC, a), |a| super::write(&GD_FC, a))?;
 			Ok(())
 		}
 	}
@@ -108,8 +117,8 @@ mod todo {
 
 		#[test_case::test_case(&FC; "fc")]
 		fn parse(arc: &crate::archive::Archives) -> Result<(), Error> {
-			let data = arc.get_decomp("t_btlset._dt").unwrap();
-			let _parsed = super::read(arc, &data)?;
+			let data = arc.get_decomp("t_btlset._dt")?.unwrap();
+			let _parsed = super::read(&GD_FC, &data)?;
 			Ok(())
 		}
 	}
@@ -147,14 +156,14 @@ fn quest_ed6(game: &GameData, path: impl AsRef<Path>) -> Result<(), Error> {
 }
 
 #[test_case::test_case(GameData::ZERO,     "../data/zero-gf/data/text/t_quest._dt"; "zero_gf_jp")]
-#[test_case::test_case(GameData::ZERO,     "../data/zero-gf/data_en/text/t_quest._dt"; "zero_gf_en")]
+#[test_case::test_case(GameData::ZERO_GF,  "../data/zero-gf/data_en/text/t_quest._dt"; "zero_gf_en")]
 #[test_case::test_case(GameData::ZERO_KAI, "../data/zero/data/text/t_quest._dt"; "zero_nisa_jp")]
 #[test_case::test_case(GameData::ZERO_KAI, "../data/zero/data/text_us/t_quest._dt"; "zero_nisa_en")]
 #[test_case::test_case(GameData::ZERO_EVO, "../data/vita/extract/zero/data/data/text/t_quest._dt"; "zero_evo")]
 
 #[test_case::test_case(GameData::AO,     "../data/ao-psp/PSP_GAME/USRDIR/data/text/t_quest._dt"; "ao_psp")]
-// #[test_case::test_case(GameData::AO,     "../data/ao-gf/data/text/t_quest._dt"; "ao_gf_cn")]
-#[test_case::test_case(GameData::AO,     "../data/ao-gf/data_en/text/t_quest._dt"; "ao_gf_en")]
+#[test_case::test_case(GameData::AO_CN,  "../data/ao-gf/data/text/t_quest._dt"; "ao_gf_cn")]
+#[test_case::test_case(GameData::AO_GF,  "../data/ao-gf/data_en/text/t_quest._dt"; "ao_gf_en")]
 #[test_case::test_case(GameData::AO_EVO, "../data/vita/extract/ao/data/data/text/t_quest._dt"; "ao_evo")]
 
 fn quest_ed7(game: &GameData, path: impl AsRef<Path>) -> Result<(), Error> {
@@ -169,14 +178,14 @@ fn quest_ed7(game: &GameData, path: impl AsRef<Path>) -> Result<(), Error> {
 
 
 #[test_case::test_case(GameData::ZERO,     Lenient, "../data/zero-gf/data/text/t_name._dt"; "zero_gf_jp")]
-#[test_case::test_case(GameData::ZERO,     Strict, "../data/zero-gf/data_en/text/t_name._dt"; "zero_gf_en")]
+#[test_case::test_case(GameData::ZERO_GF,  Strict, "../data/zero-gf/data_en/text/t_name._dt"; "zero_gf_en")]
 #[test_case::test_case(GameData::ZERO_KAI, Strict, "../data/zero/data/text/t_name._dt"; "zero_nisa_jp")]
 #[test_case::test_case(GameData::ZERO_KAI, Strict, "../data/zero/data/text_us/t_name._dt"; "zero_nisa_en")]
 #[test_case::test_case(GameData::ZERO_EVO, Strict, "../data/vita/extract/zero/data/data/text/t_name._dt"; "zero_evo")]
 
 #[test_case::test_case(GameData::AO,     Strict, "../data/ao-psp/PSP_GAME/USRDIR/data/text/t_name._dt"; "ao_psp")]
-// #[test_case::test_case(GameData::AO,     Strict, "../data/ao-gf/data/text/t_name._dt"; "ao_gf_cn")]
-#[test_case::test_case(GameData::AO,     Strict, "../data/ao-gf/data_en/text/t_name._dt"; "ao_gf_en")]
+#[test_case::test_case(GameData::AO_CN,  Strict, "../data/ao-gf/data/text/t_name._dt"; "ao_gf_cn")]
+#[test_case::test_case(GameData::AO_GF,  Strict, "../data/ao-gf/data_en/text/t_name._dt"; "ao_gf_en")]
 #[test_case::test_case(GameData::AO_EVO, Strict, "../data/vita/extract/ao/data/data/text/t_name._dt"; "ao_evo")]
 
 fn name_ed7(game: &GameData, strict: Strictness, path: impl AsRef<Path>) -> Result<(), Error> {
@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use themelios::gamedata::{ED7Lookup, Lookup};
+use themelios::vfs::Dir;
+use crate::util::*;
+
+#[test_case::test_case("../data/zero-gf/data"; "zero_gf")]
+#[test_case::test_case("../data/zero/data"; "zero_nisa")]
+#[test_case::test_case("../data/vita/extract/zero/data1/data"; "zero_evo")]
+#[test_case::test_case("../data/ao-psp/PSP_GAME/USRDIR/data"; "ao_psp")]
+#[test_case::test_case("../data/ao-gf/data"; "ao_gf")]
+#[test_case::test_case("../data/vita/extract/ao/data1/data"; "ao_evo")]
+fn ed7(path: &str) -> Result<(), Error> {
+	let dir = Dir::new(path)?;
+	let mut failed = false;
+	// Number of files in each top-level directory, and how many of them have an index
+	let mut counts = BTreeMap::<&str, (usize, usize)>::new();
+	for name in dir.list() {
+		let top = name.split_once('/').map_or("", |a| a.0);
+		let count = counts.entry(top).or_default();
+		count.0 += 1;
+		let Ok(index) = ED7Lookup.index(name) else { continue };
+		count.1 += 1;
+		// Alias spellings like `_0` or uppercase digits are accepted by index() but not given by
+		// name(), so only check that the canonical name gives the same index
+		let name2 = ED7Lookup.name(index)?;
+		if name2 != name {
+			println!("{name}: alias of {name2}");
+		}
+		if ED7Lookup.index(&name2)? != index {
+			println!("{name}: 0x{index:08X} gives {name2}");
+			failed = true;
+		}
+	}
+	for top in ["chr", "apl", "monster", "scena", "battle", "visual", "ops", "effect", "map", "bgm", "se", "text"] {
+		if let Some(&(total, found)) = counts.get(top) {
+			println!("{top}: {found} of {total}");
+			if found == 0 {
+				failed = true;
+			}
+		}
+	}
+	assert!(!failed);
+	Ok(())
+}
@@ -3,7 +3,8 @@ mod ed6 {
 	use themelios::scena::code::InstructionSet;
 	use themelios::scena::code::decompile::fixup_eddec;
 	use crate::util::*;
-	use themelios::gamedata::{Lookup, GameData};
+	use themelios::gamedata::{Lookup, LookupRef, GameData};
+	use themelios::util::Encoding;
 
 	macro_rules! test {
 		($a:item) => {
@@ -22,7 +23,7 @@ mod ed6 {
 	#[test_case::test_case(InstructionSet::Sc,    &*SC, Lenient, "../data/sc-voice/scena/", "._SN"; "sc_voice")]
 	#[test_case::test_case(InstructionSet::Tc,    &*TC, Lenient, "../data/3rd-voice/scena/", "._SN"; "tc_voice")]
 	fn roundtrip(iset: InstructionSet, lookup: &dyn Lookup, strict: Strictness, scenapath: &str, suffix: &str) -> Result<(), Error> {
-		let game = GameData { iset, lookup, kai: false };
+		let game = GameData { iset, lookup: LookupRef::Borrowed(lookup), kai: false, encoding: Encoding::Cp932, geofront: false };
 		let mut failed = false;
 
 		let mut paths = std::fs::read_dir(scenapath)?
@@ -52,7 +53,7 @@ mod ed6 {
 
 	test! {
 	fn decompile(iset: InstructionSet, lookup: &dyn Lookup, _strict: Strictness, scenapath: &str, suffix: &str) -> Result<(), Error> {
-		let game = GameData { iset, lookup, kai: false };
+		let game = GameData { iset, lookup: LookupRef::Borrowed(lookup), kai: false, encoding: Encoding::Cp932, geofront: false };
 		let mut failed = false;
 
 		let mut paths = std::fs::read_dir(scenapath)?
@@ -104,11 +105,58 @@ mod ed6 {
 	}
 	}
 
+	test! {
+	fn detect(iset: InstructionSet, _lookup: &dyn Lookup, _strict: Strictness, scenapath: &str, suffix: &str) -> Result<(), Error> {
+		let mut failed = false;
+
+		for (name, path) in files(scenapath, suffix)? {
+			let name = name.as_str();
+			let data = std::fs::read(&path)?;
+
+			let candidates = themelios::scena::detect::detect(&data);
+			let own = candidates.iter().find(|a| a.iset == iset).unwrap();
+			if own.evidence.score() < candidates[0].evidence.score() {
+				println!("{name}: detected as {}, but {own}", candidates[0]);
+				failed = true;
+			}
+		}
+
+		assert!(!failed);
+		Ok(())
+	}
+	}
+
+	test! {
+	fn translate(iset: InstructionSet, lookup: &dyn Lookup, _strict: Strictness, scenapath: &str, suffix: &str) -> Result<(), Error> {
+		use themelios::scena::translate;
+		let game = GameData { iset, lookup: LookupRef::Borrowed(lookup), kai: false, encoding: Encoding::Cp932, geofront: false };
+		let mut failed = false;
+
+		for (name, path) in files(scenapath, suffix)? {
+			let name = name.as_str();
+			let scena = themelios::scena::ed6::read(&game, &std::fs::read(&path)?)?;
+			let strings = translate::extract_ed6(&game, name, &scena).into_iter().map(|e| (e.id, e.text)).collect();
+			let mut scena2 = scena.clone();
+			for err in translate::apply_ed6(&game, name, &mut scena2, &strings) {
+				println!("{err}");
+				failed = true;
+			}
+			if scena2 != scena {
+				println!("{name}: changed by reimporting its own strings");
+				failed = true;
+			}
+		}
+
+		assert!(!failed);
+		Ok(())
+	}
+	}
+
 	#[test_case::test_case(InstructionSet::Fc, &*FC, "../data/fc.extract/01/", "../data/fc-voice/scena/";  "fc")]
 	#[test_case::test_case(InstructionSet::Sc, &*SC, "../data/sc.extract/21/", "../data/sc-voice/scena/";  "sc")]
 	#[test_case::test_case(InstructionSet::Tc, &*TC, "../data/3rd.extract/21/","../data/3rd-voice/scena/"; "tc")]
 	fn eddec(iset: InstructionSet, lookup: &dyn Lookup, vanilla: impl AsRef<Path>, voice: impl AsRef<Path>) -> Result<(), Error> {
-		let game = GameData { iset, lookup, kai: false };
+		let game = GameData { iset, lookup: LookupRef::Borrowed(lookup), kai: false, encoding: Encoding::Cp932, geofront: false };
 		let mut failed = false;
 
 		let mut paths = std::fs::read_dir(voice)?
@@ -203,13 +251,13 @@ mod ed7 {
 	macro_rules! test {
 		($a:item) => {
 			#[test_case::test_case(&GameData::ZERO, Lenient, &[], "../data/zero-gf/data/scena", ".bin"; "zero_gf_jp")]
-			#[test_case::test_case(&GameData::ZERO, Lenient, &[], "../data/zero-gf/data_en/scena", ".bin"; "zero_gf_en")]
+			#[test_case::test_case(&GameData::ZERO_GF, Lenient, &[], "../data/zero-gf/data_en/scena", ".bin"; "zero_gf_en")]
 			#[test_case::test_case(&GameData::ZERO_KAI, Strict, &["c1440.bin"], "../data/zero/data/scena", ".bin"; "zero_nisa_jp")]
 			#[test_case::test_case(&GameData::ZERO_KAI, Strict, &[], "../data/zero/data/scena_us", ".bin"; "zero_nisa_en")]
 			#[test_case::test_case(&GameData::ZERO_EVO, Strict, &["c1440.bin"], "../data/vita/extract/zero/data1/data/scena", ".bin"; "zero_evo")]
 			#[test_case::test_case(&GameData::AO, Strict, &[], "../data/ao-psp/PSP_GAME/USRDIR/data/scena", ".bin"; "ao_psp")]
 			#[test_case::test_case(&GameData::AO_EVO, Strict, &[], "../data/vita/extract/ao/data1/data/scena", ".bin"; "ao_evo")]
-			#[test_case::test_case(&GameData::AO, Lenient, &[], "../data/ao-gf/data_en/scena", ".bin"; "ao_gf_en")]
+			#[test_case::test_case(&GameData::AO_GF, Lenient, &[], "../data/ao-gf/data_en/scena", ".bin"; "ao_gf_en")]
 			$a
 		}
 	}
@@ -248,4 +296,29 @@ mod ed7 {
 		Ok(())
 	}
 	}
+
+	test! {
+	fn translate(game: &GameData, _strict: Strictness, _except: &[&str], scenapath: &str, suffix: &str) -> Result<(), Error> {
+		use themelios::scena::translate;
+		let mut failed = false;
+
+		for (name, path) in files(scenapath, suffix)? {
+			let name = name.as_str();
+			let scena = themelios::scena::ed7::read(game, &std::fs::read(&path)?)?;
+			let strings = translate::extract_ed7(game, name, &scena).into_iter().map(|e| (e.id, e.text)).collect();
+			let mut scena2 = scena.clone();
+			for err in translate::apply_ed7(game, name, &mut scena2, &strings) {
+				println!("{err}");
+				failed = true;
+			}
+			if scena2 != scena {
+				println!("{name}: changed by reimporting its own strings");
+				failed = true;
+			}
+		}
+
+		assert!(!failed);
+		Ok(())
+	}
+	}
 }
@@ -1,6 +1,7 @@
+use std::path::PathBuf;
 use std::time::{Instant, Duration};
 
-use themelios::{archive::Archives, gamedata::GameData, scena::code::InstructionSet};
+use themelios::{archive::Archives, gamedata::{GameData, LookupRef}, scena::code::InstructionSet, util::Encoding};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -10,6 +11,9 @@ pub enum Error {
 	#[error("{source}")]
 	Lookup { #[from] source: themelios::gamedata::LookupError, backtrace: std::backtrace::Backtrace },
 
+	#[error(transparent)]
+	Archive { #[from] #[backtrace] source: themelios::archive::Error },
+
 	#[error(transparent)]
 	Read { #[from] #[backtrace] source: themelios::util::ReadError },
 
@@ -29,32 +33,30 @@ impl std::convert::From<String> for Error {
 	}
 }
 
-// Sync wrapper. It's not safe at all.
-pub struct SW<T>(T);
-
-impl <T> std::ops::Deref for SW<T> {
-	type Target = T;
-
-	fn deref(&self) -> &T {
-		&self.0
-	}
-}
-
-// SAFETY: None.
-unsafe impl <T> Sync for SW<T> {}
-
 lazy_static::lazy_static! {
 	pub static ref FC: Archives = Archives::new("../data/fc").unwrap();
 	pub static ref SC: Archives = Archives::new("../data/sc").unwrap();
 	pub static ref TC: Archives = Archives::new("../data/3rd").unwrap();
 
-	pub static ref GD_FC: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::Fc, lookup: &*FC, kai: false });
-	pub static ref GD_SC: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::Sc, lookup: &*SC, kai: false });
-	pub static ref GD_TC: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::Tc, lookup: &*TC, kai: false });
+	pub static ref GD_FC: GameData<'static> = GameData { iset: InstructionSet::Fc, lookup: LookupRef::Borrowed(&*FC), kai: false, encoding: Encoding::Cp932, geofront: false };
+	pub static ref GD_SC: GameData<'static> = GameData { iset: InstructionSet::Sc, lookup: LookupRef::Borrowed(&*SC), kai: false, encoding: Encoding::Cp932, geofront: false };
+	pub static ref GD_TC: GameData<'static> = GameData { iset: InstructionSet::Tc, lookup: LookupRef::Borrowed(&*TC), kai: false, encoding: Encoding::Cp932, geofront: false };
+
+	pub static ref GD_FC_EVO: GameData<'static> = GameData { iset: InstructionSet::FcEvo, lookup: LookupRef::Borrowed(&*FC), kai: false, encoding: Encoding::Cp932, geofront: false };
+	pub static ref GD_SC_EVO: GameData<'static> = GameData { iset: InstructionSet::ScEvo, lookup: LookupRef::Borrowed(&*SC), kai: false, encoding: Encoding::Cp932, geofront: false };
+	pub static ref GD_TC_EVO: GameData<'static> = GameData { iset: InstructionSet::TcEvo, lookup: LookupRef::Borrowed(&*TC), kai: false, encoding: Encoding::Cp932, geofront: false };
+}
 
-	pub static ref GD_FC_EVO: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::FcEvo, lookup: &*FC, kai: false });
-	pub static ref GD_SC_EVO: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::ScEvo, lookup: &*SC, kai: false });
-	pub static ref GD_TC_EVO: SW<GameData<'static>> = SW(GameData { iset: InstructionSet::TcEvo, lookup: &*TC, kai: false });
+/// The files in `dir` whose names end with `suffix`, sorted by path.
+pub fn files(dir: &str, suffix: &str) -> Result<Vec<(String, PathBuf)>, Error> {
+	let mut paths = std::fs::read_dir(dir)?
+		.map(|r| r.map(|a| a.path()))
+		.collect::<Result<Vec<_>, _>>()?;
+	paths.sort();
+	Ok(paths.into_iter().filter_map(|path| {
+		let name = path.file_name()?.to_str()?.to_owned();
+		name.ends_with(suffix).then_some((name, path))
+	}).collect())
 }
 
 pub fn check_equal<T: PartialEq + std::fmt::Debug>(a: &T, b: &T) -> Result<(), Error> {
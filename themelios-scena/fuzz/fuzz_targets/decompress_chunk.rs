@@ -0,0 +1,21 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use themelios_scena::decompress;
+
+fuzz_target!(|data: &[u8]| {
+	let limits = decompress::Limits::default();
+	if let Ok(out) = decompress::decompress_chunk_with(data, &limits) {
+		assert!(out.len() <= limits.chunk);
+	}
+
+	let mut out = Vec::new();
+	for chunk in decompress::chunks(data).into_iter().flatten() {
+		if decompress::decompress_chunk_into_with(chunk, &mut out, &limits).is_err() {
+			break
+		}
+	}
+	if let Ok(out) = decompress::decompress_with(data, &limits) {
+		assert!(out.len() <= limits.total);
+		assert_eq!(decompress::decompressed_size(data).ok(), Some(out.len()));
+	}
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use std::io::Read;
+use libfuzzer_sys::fuzz_target;
+use themelios_scena::decompress;
+
+fuzz_target!(|data: &[u8]| {
+	let limits = decompress::Limits::default();
+	let mut total = 0;
+	for chunk in decompress::decompress_stream_with(&mut &data[..], limits) {
+		let Ok(chunk) = chunk else { break };
+		assert!(chunk.len() <= limits.chunk);
+		total += chunk.len();
+	}
+	assert!(total <= limits.total);
+
+	let mut out = Vec::new();
+	let mut reader = decompress::Reader::with_limits(data, limits);
+	if reader.read_to_end(&mut out).is_ok() {
+		assert!(out.len() <= limits.total);
+		assert_eq!(decompress::decompress_with(data, &limits).ok(), Some(out));
+	}
+});
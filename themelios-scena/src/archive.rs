@@ -1,19 +1,42 @@
 use std::{
-	collections::HashMap,
+	collections::{HashMap, BTreeMap},
 	path::{Path, PathBuf},
-	io,
-	fs::File,
+	io::{self, Read, Write, Seek, SeekFrom},
+	fs::{self, File, OpenOptions},
 	ops::Range,
 };
+#[cfg(feature = "mmap")]
 use mapr::Mmap;
-use hamu::read::coverage::*;
-use hamu::read::le::*;
+use once_cell::sync::OnceCell;
+use hamu::write::le::*;
 
 use crate::decompress;
-use crate::util;
+use crate::util::{self, cast};
+
+pub mod diff;
+pub mod validate;
+mod cache;
 
 type Backtrace = Box<std::backtrace::Backtrace>;
 
+/// Size of the header of both .dir and .dat files: an 8-byte magic and a u64 entry count.
+const HEADER_SIZE: usize = 16;
+/// Size of each entry's record in the .dir.
+const DIR_RECORD_SIZE: usize = 36;
+/// Where in a .dir record the length, timestamp and offset fields are.
+const DIR_RECORD_LOCATION: usize = 24;
+
+/// Position of the `index`th record in the .dir.
+fn dir_record(index: usize) -> usize {
+	HEADER_SIZE + index * DIR_RECORD_SIZE
+}
+
+/// Position of the `index`th u32 in the .dat's offset table, which holds the start of the data
+/// followed by the end of each entry.
+fn dat_offset(index: usize) -> usize {
+	HEADER_SIZE + index * 4
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("{source}")]
@@ -22,9 +45,30 @@ pub enum Error {
 	#[error("{source}")]
 	Io { #[from] source: std::io::Error, backtrace: Backtrace },
 
+	#[error("{source}")]
+	Write { #[from] source: hamu::write::Error, backtrace: Backtrace },
+
 	#[error("{source}")]
 	Encoding { #[from] source: util::DecodeError, backtrace: Backtrace },
 
+	#[error("{source}")]
+	Cast { #[from] source: util::CastError, backtrace: Backtrace },
+
+	#[error("invalid archive entry name {name:?}")]
+	Name { name: String, backtrace: Backtrace },
+
+	#[error("no archive entry named {name:?}")]
+	NotFound { name: String, backtrace: Backtrace },
+
+	#[error("could not decompress {name:?}\n{source}")]
+	Decompress { name: String, source: std::io::Error, backtrace: Backtrace },
+
+	#[error("the given files are not the ones the archive was read from")]
+	Mismatch { backtrace: Backtrace },
+
+	#[error("invalid archive\n{report}")]
+	Invalid { report: validate::Report, backtrace: Backtrace },
+
 	#[error("while reading {}\n{source}", dirpath.display())]
 	Archive {
 		#[backtrace]
@@ -33,13 +77,61 @@ pub enum Error {
 	},
 }
 
-#[derive(Debug)]
-pub struct Archive {
-	dat: Mmap,
+/// An archive, with its .dat contents held in `D`.
+///
+/// Archives opened from files use [`Dat`], but any byte buffer can be used with
+/// [`from_bytes`](Archive::from_bytes), such as a `Vec<u8>` or `&[u8]`.
+pub struct Archive<D = Dat> {
+	dat: D,
 	names: HashMap<String, usize>,
 	entries: Vec<Entry>,
 }
 
+impl<D: AsRef<[u8]>> std::fmt::Debug for Archive<D> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Archive")
+			.field("dat", &format_args!("[{} bytes]", self.dat.as_ref().len()))
+			.field("entries", &self.entries)
+			.finish()
+	}
+}
+
+/// The contents of a file opened by [`Archive`]. Memory-mapped if the `mmap` feature is enabled,
+/// otherwise read into memory.
+#[derive(Debug)]
+pub enum Dat {
+	#[cfg(feature = "mmap")]
+	Mmap(Mmap),
+	Owned(Vec<u8>),
+}
+
+impl Dat {
+	pub fn open(file: &File) -> io::Result<Self> {
+		#[cfg(feature = "mmap")]
+		{
+			Ok(Dat::Mmap(unsafe { Mmap::map(file)? }))
+		}
+		#[cfg(not(feature = "mmap"))]
+		{
+			let mut file = file;
+			let mut data = Vec::new();
+			file.seek(SeekFrom::Start(0))?;
+			file.read_to_end(&mut data)?;
+			Ok(Dat::Owned(data))
+		}
+	}
+}
+
+impl AsRef<[u8]> for Dat {
+	fn as_ref(&self) -> &[u8] {
+		match self {
+			#[cfg(feature = "mmap")]
+			Dat::Mmap(a) => a,
+			Dat::Owned(a) => a,
+		}
+	}
+}
+
 #[derive(Clone, Debug)]
 pub struct Entry {
 	pub index: usize,
@@ -76,68 +168,103 @@ impl Archive {
 	}
 
 	pub fn from_dir_dat(dir: &File, dat: &File) -> Result<Archive, Error> {
-		let dir = unsafe { Mmap::map(dir)? };
-		let dat = unsafe { Mmap::map(dat)? };
+		let (archive, report) = Self::from_dir_dat_lenient(dir, dat)?;
+		if !report.is_ok() {
+			return Err(Error::Invalid {
+				report,
+				backtrace: std::backtrace::Backtrace::capture().into(),
+			})
+		}
+		Ok(archive)
+	}
 
-		let mut names = HashMap::new();
-		let mut entries = Vec::new();
+	/// Reads an archive even if it is malformed, returning a report of everything wrong with it.
+	///
+	/// Entries pointing outside the .dat are truncated, and undecodable names are decoded lossily.
+	pub fn from_dir_dat_lenient(dir: &File, dat: &File) -> Result<(Archive, validate::Report), Error> {
+		Ok(Archive::from_bytes_lenient(Dat::open(dir)?.as_ref(), Dat::open(dat)?))
+	}
 
-		{
-			let mut dir = Coverage::new(Bytes::new(&dir));
-			let mut dat = Coverage::new(Bytes::new(&dat));
-			dir.check(b"LB DIR\x1A\0")?;
-			dat.check(b"LB DAT\x1A\0")?;
-			let count = dir.u64()?;
-			dat.check_u64(count)?;
-			dat.check_u32(20 + count as u32 * 4)?;
-
-			for index in 0..count as usize {
-				let name = dir.slice(12)?;
-				let name = util::decode(name)?;
-				let name = if let Some((name, ext)) = name.split_once('.') {
-					format!("{}.{}", name.trim_end(), ext)
-				} else {
-					name.to_owned()
-				};
-				let name = name.to_lowercase();
-
-				let unk1 = dir.u32()?; // Zero in all but a few files in 3rd; in those cases it looks kinda like a timestamp
-				let unk2 = dir.u32()? as usize;
-				let unk3 = dir.u32()? as usize;
-				let len = dir.u32()? as usize;
-				let timestamp = dir.u32()?;
-				let offset = dir.u32()? as usize;
-
-				dat.check_u32((offset+len) as u32)?;
-				dat.clone().at(offset)?.slice(len)?;
-
-				let entry = Entry {
-					index,
-					name: name.to_owned(),
-					unk1,
-					unk2,
-					unk3,
-					timestamp,
-					range: offset..offset+len,
-				};
-
-				entries.push(entry);
-				if name != "/_______.___" {
-					names.insert(name.to_owned(), index);
-				}
-			}
+	/// Checks an archive for problems, without keeping it open.
+	pub fn validate(dir: &File, dat: &File) -> Result<validate::Report, Error> {
+		Ok(Self::from_dir_dat_lenient(dir, dat)?.1)
+	}
 
-			assert!(dir.uncovered().is_empty());
-			assert!(dat.uncovered().is_empty());
+	/// Replaces an entry's data without rewriting the whole archive.
+	///
+	/// The new data is appended to the end of the `.dat`, and the `.dir` record is updated to point
+	/// to it. The entry keeps its index, so file references to it stay valid. The old data is left
+	/// in place as dead space, which is a [gap](validate::Problem::Gap), so the archive can
+	/// afterwards only be opened with [`from_dir_dat_lenient`](Self::from_dir_dat_lenient) or
+	/// [`Archives::new_lenient`].
+	///
+	/// `dir` and `dat` must be the files this archive was read from, opened for both reading and
+	/// writing. As a sanity check, the entry's record in `dir` and the length of `dat` must match
+	/// what the archive has.
+	///
+	/// This is not atomic. Everything that can be checked up front is checked before anything is
+	/// written, so errors like a mismatch or an archive growing past 4 GiB leave the files
+	/// untouched, but an I/O error partway through can leave the archive half-updated.
+	pub fn replace(&mut self, dir: &File, dat: &File, name: &str, data: &[u8], timestamp: u32) -> Result<(), Error> {
+		let index = self.index(name).ok_or_else(|| Error::NotFound {
+			name: name.to_owned(),
+			backtrace: std::backtrace::Backtrace::capture().into(),
+		})?;
+
+		let mut dir_ = dir;
+		let record_pos = (dir_record(index) + DIR_RECORD_LOCATION) as u64;
+		let mut record = [0; 12];
+		dir_.seek(SeekFrom::Start(record_pos))?;
+		dir_.read_exact(&mut record)?;
+		let field = |i: usize| u32::from_le_bytes(record[i..i+4].try_into().unwrap()) as usize;
+		let range = field(8)..field(8)+field(0);
+		if range != self.entries[index].range || dat.metadata()?.len() != self.dat.as_ref().len() as u64 {
+			return Err(Error::Mismatch {
+				backtrace: std::backtrace::Backtrace::capture().into(),
+			})
 		}
 
-		Ok(Archive {
-			dat,
-			names,
-			entries,
-		})
+		let offset = self.dat.as_ref().len();
+		let end: u32 = cast(offset + data.len())?;
+		record[0..4].copy_from_slice(&u32::to_le_bytes(cast(data.len())?));
+		record[4..8].copy_from_slice(&u32::to_le_bytes(timestamp));
+		record[8..12].copy_from_slice(&u32::to_le_bytes(cast(offset)?));
+
+		let mut dat_ = dat;
+		dat_.seek(SeekFrom::Start(offset as u64))?;
+		dat_.write_all(data)?;
+		dir_.seek(SeekFrom::Start(record_pos))?;
+		dir_.write_all(&record)?;
+
+		dat_.seek(SeekFrom::Start(dat_offset(index + 1) as u64))?;
+		dat_.write_all(&u32::to_le_bytes(end))?;
+
+		self.dat = Dat::open(dat)?;
+		let entry = &mut self.entries[index];
+		entry.timestamp = timestamp;
+		entry.range = offset..offset+data.len();
+		Ok(())
 	}
+}
 
+impl<D: AsRef<[u8]>> Archive<D> {
+	/// Reads an archive from memory, without any filesystem access.
+	pub fn from_bytes(dir: &[u8], dat: D) -> Result<Self, Error> {
+		let (archive, report) = Self::from_bytes_lenient(dir, dat);
+		if !report.is_ok() {
+			return Err(Error::Invalid {
+				report,
+				backtrace: std::backtrace::Backtrace::capture().into(),
+			})
+		}
+		Ok(archive)
+	}
+
+	/// Like [`from_dir_dat_lenient`](Archive::from_dir_dat_lenient), but from memory.
+	pub fn from_bytes_lenient(dir: &[u8], dat: D) -> (Self, validate::Report) {
+		let (names, entries, report) = validate::read(dir, dat.as_ref());
+		(Archive { dat, names, entries }, report)
+	}
 
 	pub fn name(&self, index: usize) -> Option<&str> {
 		let ent = self.entries.get(index)?;
@@ -155,7 +282,7 @@ impl Archive {
 
 	pub fn get(&self, name: &str) -> Option<&[u8]> {
 		let ent = self.entry(name)?;
-		Some(&self.dat[ent.range.clone()])
+		Some(&self.dat.as_ref()[ent.range.clone()])
 	}
 
 	pub fn entries(&self) -> &[Entry] {
@@ -163,81 +290,521 @@ impl Archive {
 	}
 }
 
+/// A file to be written into an archive by [`ArchiveBuilder`].
+#[derive(Clone, Debug)]
+pub struct BuildEntry {
+	pub name: String,
+	pub data: Vec<u8>,
+	pub unk1: u32,
+	pub unk2: usize,
+	pub unk3: usize,
+	pub timestamp: u32,
+}
+
+impl BuildEntry {
+	pub fn new(name: impl Into<String>, data: impl Into<Vec<u8>>, timestamp: u32) -> Self {
+		BuildEntry {
+			name: name.into(),
+			data: data.into(),
+			unk1: 0,
+			unk2: 0,
+			unk3: 0,
+			timestamp,
+		}
+	}
+}
+
+/// Writes `.dir`/`.dat` pairs that can be read by [`Archive`].
+///
+/// Entries keep the index they were pushed at, which is what file references elsewhere refer to.
+/// Empty slots are written as the `/_______.___` placeholders seen in the vanilla archives.
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveBuilder {
+	entries: Vec<Option<BuildEntry>>,
+}
+
+impl ArchiveBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates a builder containing all entries of an existing archive, including placeholders.
+	pub fn from_archive<D: AsRef<[u8]>>(arc: &Archive<D>) -> Self {
+		let entries = arc.entries().iter().map(|e| {
+			(e.name != "/_______.___").then(|| BuildEntry {
+				name: e.name.clone(),
+				data: arc.dat.as_ref()[e.range.clone()].to_owned(),
+				unk1: e.unk1,
+				unk2: e.unk2,
+				unk3: e.unk3,
+				timestamp: e.timestamp,
+			})
+		}).collect();
+		ArchiveBuilder { entries }
+	}
+
+	/// Adds an entry, returning its index.
+	pub fn push(&mut self, entry: BuildEntry) -> usize {
+		self.entries.push(Some(entry));
+		self.entries.len() - 1
+	}
+
+	/// Adds an empty placeholder slot, returning its index.
+	pub fn push_placeholder(&mut self) -> usize {
+		self.entries.push(None);
+		self.entries.len() - 1
+	}
+
+	pub fn entries(&self) -> &[Option<BuildEntry>] {
+		&self.entries
+	}
+
+	pub fn entries_mut(&mut self) -> &mut Vec<Option<BuildEntry>> {
+		&mut self.entries
+	}
+
+	pub fn entry_mut(&mut self, name: &str) -> Option<&mut BuildEntry> {
+		self.entries.iter_mut().flatten().find(|e| e.name == name)
+	}
+
+	pub fn write(&self, dir: &mut impl Write, dat: &mut impl Write) -> Result<(), Error> {
+		let count = self.entries.len();
+		let mut f = OutBytes::new();
+		let mut g = OutBytes::new();
+		f.slice(b"LB DIR\x1A\0");
+		g.slice(b"LB DAT\x1A\0");
+		f.u64(count as u64);
+		g.u64(count as u64);
+
+		let mut offset = dat_offset(count + 1);
+		g.u32(cast(offset)?);
+		for entry in &self.entries {
+			match entry {
+				Some(e) => {
+					f.slice(&raw_name(&e.name)?);
+					f.u32(e.unk1);
+					f.u32(cast(e.unk2)?);
+					f.u32(cast(e.unk3)?);
+					f.u32(cast(e.data.len())?);
+					f.u32(e.timestamp);
+					f.u32(cast(offset)?);
+					offset += e.data.len();
+				}
+				None => {
+					f.slice(b"/_______.___");
+					f.u32(0);
+					f.u32(0);
+					f.u32(0);
+					f.u32(0);
+					f.u32(0);
+					f.u32(cast(offset)?);
+				}
+			}
+			g.u32(cast(offset)?);
+		}
+
+		dir.write_all(&f.finish()?)?;
+		dat.write_all(&g.finish()?)?;
+		for e in self.entries.iter().flatten() {
+			dat.write_all(&e.data)?;
+		}
+		Ok(())
+	}
+
+	pub fn save(&self, path: impl AsRef<Path>, num: u8) -> Result<(), Error> {
+		let (dirpath, datpath) = Archive::dir_dat(path, num);
+		let mut dir = io::BufWriter::new(File::create(dirpath)?);
+		let mut dat = io::BufWriter::new(File::create(datpath)?);
+		self.write(&mut dir, &mut dat)?;
+		dir.flush()?;
+		dat.flush()?;
+		Ok(())
+	}
+}
+
+/// Converts a name like `t0100._sn` into the on-disk `T0100   ._SN` format.
+fn raw_name(name: &str) -> Result<[u8; 12], Error> {
+	let invalid = || Error::Name {
+		name: name.to_owned(),
+		backtrace: std::backtrace::Backtrace::capture().into(),
+	};
+	if !name.is_ascii() {
+		return Err(invalid())
+	}
+	let name_ = name.to_uppercase();
+	let raw = if let Some((name, ext)) = name_.split_once('.') {
+		if name.len() > 8 || ext.len() > 3 {
+			return Err(invalid())
+		}
+		format!("{name:<8}.{ext:<3}")
+	} else {
+		if name_.len() > 12 {
+			return Err(invalid())
+		}
+		format!("{name_:<12}")
+	};
+	Ok(raw.as_bytes().try_into().unwrap())
+}
+
 // TODO should this even be part of this? Not sure if it's general enough to be meaningful.
+/// All the archives of a game, which are opened lazily when a file in them is first accessed.
 #[derive(Debug)]
 pub struct Archives {
+	path: PathBuf,
 	names: HashMap<String, u16>,
-	archives: HashMap<u16, Archive>,
+	archives: BTreeMap<u16, OnceCell<Archive>>,
 }
 
+// Archives are often shared between threads, such as when extracting in parallel.
+const _: () = {
+	const fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<Archives>();
+};
+
 impl Archives {
+	/// Indexes the archives in a directory.
+	///
+	/// Only the names are read from the .dir files; each archive is fully opened when first
+	/// needed, so errors in opening them are returned by whichever method needed it first.
+	/// [`open_all`](Self::open_all) can be used to check them all up front.
+	///
+	/// A directory that does not exist gives an empty set of archives.
 	pub fn new(path: impl AsRef<Path>) -> Result<Self, Error> {
-		let mut names = HashMap::new();
-		let mut archives = HashMap::new();
-		for num in 0..=255u16 {
-			let (dirpath, datpath) = Archive::dir_dat(&path, num as u8);
-			let dir = match File::open(&dirpath) {
-				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
-				e => e?,
-			};
-			let dat = File::open(datpath)?;
-			let arch = Archive::from_dir_dat(&dir, &dat).map_err(|e| Error::Archive { dirpath, source: e.into() })?;
-			for ent in arch.entries() {
-				names.insert(ent.name.to_owned(), num);
+		Self::scan(path.as_ref(), None)
+	}
+
+	/// Like [`new`](Self::new), but saves the index to a file and reuses it on later calls, as long
+	/// as none of the .dir files have changed. This avoids reading the .dir files at all.
+	///
+	/// Failing to write the cache file is not an error.
+	pub fn new_cached(path: impl AsRef<Path>, cache: impl AsRef<Path>) -> Result<Self, Error> {
+		Self::scan(path.as_ref(), Some(cache.as_ref()))
+	}
+
+	/// Like [`new`](Self::new), but opens all archives immediately, reading malformed archives with
+	/// [`Archive::from_dir_dat_lenient`].
+	///
+	/// Returns the report for each archive that had any problems.
+	pub fn new_lenient(path: impl AsRef<Path>) -> Result<(Self, Vec<(u8, validate::Report)>), Error> {
+		let this = Self::new(path)?;
+		let mut reports = Vec::new();
+		for (&num, cell) in &this.archives {
+			let (dirpath, datpath) = Archive::dir_dat(&this.path, num as u8);
+			let (arch, report) = File::open(&dirpath)
+				.and_then(|dir| Ok((dir, File::open(datpath)?)))
+				.map_err(Error::from)
+				.and_then(|(dir, dat)| Archive::from_dir_dat_lenient(&dir, &dat))
+				.map_err(|e| Error::Archive { dirpath, source: e.into() })?;
+			if !report.is_empty() {
+				reports.push((num as u8, report));
+			}
+			cell.set(arch).unwrap();
+		}
+		Ok((this, reports))
+	}
+
+	fn scan(path: &Path, cache: Option<&Path>) -> Result<Self, Error> {
+		let mut stamps = Vec::new();
+		let entries = match fs::read_dir(path) {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self {
+				path: path.to_owned(),
+				names: HashMap::new(),
+				archives: BTreeMap::new(),
+			}),
+			Err(e) => return Err(e.into()),
+		};
+		for ent in entries {
+			let filename = ent?.file_name();
+			let Some(num) = filename.to_str()
+				.and_then(|a| a.strip_prefix("ED6_DT")?.strip_suffix(".dir"))
+				.filter(|a| a.len() == 2)
+				.and_then(|a| u8::from_str_radix(a, 16).ok())
+			else { continue };
+			let (dirpath, datpath) = Archive::dir_dat(path, num);
+			if dirpath.file_name() != Some(filename.as_os_str()) {
+				continue
 			}
-			archives.insert(num, arch);
+			stamps.push(cache::Stamp::new(num, &dirpath, &datpath)?);
 		}
+		stamps.sort_by_key(|a| a.num);
+
+		let names = match cache.and_then(|cache| cache::load(cache, &stamps)) {
+			Some(names) => names,
+			None => {
+				let mut names = HashMap::new();
+				for stamp in &stamps {
+					let (dirpath, _) = Archive::dir_dat(path, stamp.num);
+					for name in validate::names(&fs::read(dirpath)?) {
+						names.insert(name, stamp.num as u16);
+					}
+				}
+				if let Some(cache) = cache {
+					let _ = cache::save(cache, &stamps, &names);
+				}
+				names
+			}
+		};
+
 		Ok(Self {
+			path: path.to_owned(),
 			names,
-			archives,
+			archives: stamps.iter().map(|a| (a.num as u16, OnceCell::new())).collect(),
 		})
 	}
 
-	pub fn get(&self, name: &str) -> Option<&[u8]> {
-		let arch = *self.names.get(name)?;
-		let data = self.archive(arch)?.get(name)?;
-		Some(data)
+	/// Returns the entry, or `None` if there is no such file. Fails if the archive the file is in
+	/// could not be opened.
+	pub fn entry(&self, name: &str) -> Result<Option<&Entry>, Error> {
+		let Some(&arch) = self.names.get(name) else { return Ok(None) };
+		Ok(self.archive(arch)?.and_then(|a| a.entry(name)))
 	}
 
-	pub fn get_decomp(&self, name: &str) -> Option<Vec<u8>> {
-		self.get(name).and_then(|a| decompress::decompress(a).ok())
+	pub fn get(&self, name: &str) -> Result<Option<&[u8]>, Error> {
+		let Some(&arch) = self.names.get(name) else { return Ok(None) };
+		Ok(self.archive(arch)?.and_then(|a| a.get(name)))
 	}
 
+	/// Like [`get`](Self::get), but decompresses the data. Files that are not compressed, as told by
+	/// [`decompress::is_compressed`], give `None`, but compressed files that fail to decompress are
+	/// an error.
+	pub fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		let Some(data) = self.get(name)? else { return Ok(None) };
+		if !decompress::is_compressed(data) {
+			return Ok(None)
+		}
+		decompress::decompress(data).map(Some).map_err(|source| Error::Decompress {
+			name: name.to_owned(),
+			source,
+			backtrace: std::backtrace::Backtrace::capture().into(),
+		})
+	}
+
+	/// Iterates over the names of all non-empty files.
+	///
+	/// Archives that fail to open are skipped, so their files are silently missing. Call
+	/// [`open_all`](Self::open_all) first where the listing needs to be complete.
 	pub fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
 		Box::new(
-			self.archives()
-			.map(|a| a.1)
+			self.archives.keys()
+			.filter_map(|&n| self.archive(n).ok()?)
 			.flat_map(|a| a.entries())
 			.filter(|a| !a.is_empty())
 			.map(|a| a.name.as_str())
 		)
 	}
 
-	pub fn name(&self, a: u32) -> Option<&str> {
+	pub fn name(&self, a: u32) -> Result<Option<&str>, Error> {
 		let index = (a & 0xFFFF) as u16;
 		let mut arch  = (a >> 16) as u16;
 		if arch == 0x1A && !self.archives.contains_key(&0x1A) {
 			arch = 0x1B;
 		}
-		let name = self.archive(arch)?.name(index as usize)?;
-		Some(name)
+		Ok(self.archive(arch)?.and_then(|a| a.name(index as usize)))
+	}
+
+	pub fn index(&self, name: &str) -> Result<Option<u32>, Error> {
+		let Some(&arch) = self.names.get(name) else { return Ok(None) };
+		let Some(index) = self.archive(arch)?.and_then(|a| a.index(name)) else { return Ok(None) };
+		let arch = if arch == 0x1B { 0x1A } else { arch };
+		Ok(Some((index as u32) | (arch as u32) << 16))
 	}
 
-	pub fn index(&self, name: &str) -> Option<u32> {
-		let mut arch = *self.names.get(name)?;
-		let index = self.archive(arch)?.index(name)?;
-		if arch == 0x1B {
-			arch = 0x1A;
+	/// Returns the given archive, opening it if necessary.
+	///
+	/// Returns `None` if it does not exist. If it fails to open, the error is returned, and the next
+	/// call tries to open it again.
+	pub fn archive(&self, n: u16) -> Result<Option<&Archive>, Error> {
+		let Some(cell) = self.archives.get(&n) else { return Ok(None) };
+		cell.get_or_try_init(|| {
+			let (dirpath, _) = Archive::dir_dat(&self.path, n as u8);
+			Archive::new(&self.path, n as u8)
+				.map_err(|e| Error::Archive { dirpath, source: e.into() })
+		}).map(Some)
+	}
+
+	/// Opens all archives that have not been opened yet, returning the first error.
+	pub fn open_all(&self) -> Result<(), Error> {
+		for &n in self.archives.keys() {
+			self.archive(n)?;
 		}
-		Some((index as u32) | (arch as u32) << 16)
+		Ok(())
 	}
 
-	pub fn archive(&self, n: u16) -> Option<&Archive> {
-		self.archives.get(&n)
+	/// Iterates over all archives, in order. They are all opened first, and the first one that
+	/// fails to open is returned as an error.
+	pub fn archives(&self) -> Result<impl Iterator<Item=(u8, &Archive)>, Error> {
+		self.open_all()?;
+		Ok(self.archives.iter().map(|(&n, cell)| (n as u8, cell.get().unwrap())))
 	}
 
-	pub fn archives(&self) -> impl Iterator<Item=(u8, &Archive)> {
-		self.archives.iter().map(|(a, b)| (*a as u8, b))
+	/// Replaces an entry's data in place; see [`Archive::replace`].
+	///
+	/// The archive is opened strictly if it is not already open, so archives that have been patched
+	/// before need to be opened with [`new_lenient`](Self::new_lenient).
+	pub fn replace(&mut self, name: &str, data: &[u8], timestamp: u32) -> Result<(), Error> {
+		let num = *self.names.get(name).ok_or_else(|| Error::NotFound {
+			name: name.to_owned(),
+			backtrace: std::backtrace::Backtrace::capture().into(),
+		})?;
+		let (dirpath, datpath) = Archive::dir_dat(&self.path, num as u8);
+		let dir = OpenOptions::new().read(true).write(true).open(&dirpath)?;
+		let dat = OpenOptions::new().read(true).write(true).open(datpath)?;
+		self.archive(num)?;
+		let arch = self.archives.get_mut(&num).unwrap().get_mut().unwrap();
+		arch.replace(&dir, &dat, name, data, timestamp)
+			.map_err(|e| Error::Archive { dirpath, source: e.into() })
 	}
 
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn build(path: &Path, num: u8, first: &[u8]) -> Result<(), Error> {
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("t0100._sn", first, 1));
+		b.push_placeholder();
+		b.push(BuildEntry::new("t_town._dt", b"second".as_slice(), 2));
+		b.save(path, num)
+	}
+
+	fn open_rw(path: &Path, num: u8) -> Result<(File, File), Error> {
+		let (dirpath, datpath) = Archive::dir_dat(path, num);
+		let dir = OpenOptions::new().read(true).write(true).open(dirpath)?;
+		let dat = OpenOptions::new().read(true).write(true).open(datpath)?;
+		Ok((dir, dat))
+	}
+
+	#[test]
+	fn replace() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		build(tmp.path(), 0, b"first")?;
+		let (dir, dat) = open_rw(tmp.path(), 0)?;
+		let mut arc = Archive::from_dir_dat(&dir, &dat)?;
+		arc.replace(&dir, &dat, "t0100._sn", b"replaced", 3)?;
+		assert_eq!(arc.get("t0100._sn"), Some(&b"replaced"[..]));
+		drop((dir, dat));
+
+		// The old data is left behind, which only the lenient constructors accept
+		assert!(matches!(Archive::new(tmp.path(), 0), Err(Error::Invalid { .. })));
+		let (dirpath, datpath) = Archive::dir_dat(tmp.path(), 0);
+		let (arc, report) = Archive::from_dir_dat_lenient(&File::open(dirpath)?, &File::open(datpath)?)?;
+		assert_eq!(report.problems, [validate::Problem::Gap { range: 32..37 }]);
+		assert_eq!(arc.index("t0100._sn"), Some(0));
+		assert_eq!(arc.get("t0100._sn"), Some(&b"replaced"[..]));
+		assert_eq!(arc.entry("t0100._sn").unwrap().timestamp, 3);
+		assert_eq!(arc.get("t_town._dt"), Some(&b"second"[..]));
+		Ok(())
+	}
+
+	#[test]
+	fn replace_mismatch() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		build(tmp.path(), 0, b"first")?;
+		build(tmp.path(), 1, b"other first")?;
+		let (dir, dat) = open_rw(tmp.path(), 0)?;
+		let mut arc = Archive::from_dir_dat(&dir, &dat)?;
+		let (dir1, dat1) = open_rw(tmp.path(), 1)?;
+		let read = |num| -> Result<_, Error> {
+			let (dirpath, datpath) = Archive::dir_dat(tmp.path(), num);
+			Ok((fs::read(dirpath)?, fs::read(datpath)?))
+		};
+		let before = (read(0)?, read(1)?);
+		let result = arc.replace(&dir1, &dat1, "t0100._sn", b"replaced", 3);
+		assert!(matches!(result, Err(Error::Mismatch { .. })), "{result:?}");
+		// Nothing is written before the check
+		assert_eq!((read(0)?, read(1)?), before);
+		assert_eq!(Archive::new(tmp.path(), 1)?.get("t0100._sn"), Some(&b"other first"[..]));
+		Ok(())
+	}
+
+	#[test]
+	fn lazy() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		build(tmp.path(), 0, b"first")?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("broken._dt", b"data".as_slice(), 1));
+		b.save(tmp.path(), 1)?;
+		let (_, datpath) = Archive::dir_dat(tmp.path(), 1);
+		fs::write(datpath, b"not an archive")?;
+
+		let arcs = Archives::new(tmp.path())?;
+		assert_eq!(arcs.get("t0100._sn")?, Some(&b"first"[..]));
+		assert!(arcs.archives[&0].get().is_some());
+		assert!(arcs.archives[&1].get().is_none());
+		assert_eq!(arcs.get("missing._dt")?, None);
+
+		// Files in the broken archive are errors, not missing
+		assert!(arcs.get("broken._dt").is_err());
+		assert!(arcs.entry("broken._dt").is_err());
+		assert!(arcs.index("broken._dt").is_err());
+		assert!(arcs.name(0x0001_0000).is_err());
+		assert!(arcs.open_all().is_err());
+		assert!(arcs.archives().is_err());
+		assert_eq!(arcs.list().collect::<Vec<_>>(), ["t0100._sn", "t_town._dt"]);
+		Ok(())
+	}
+
+	#[test]
+	fn short_names() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("abc.x", b"short extension".as_slice(), 1));
+		b.push(BuildEntry::new("noext", b"no extension".as_slice(), 2));
+		b.save(tmp.path(), 0)?;
+
+		let arc = Archive::new(tmp.path(), 0)?;
+		assert_eq!(arc.name(0), Some("abc.x"));
+		assert_eq!(arc.get("abc.x"), Some(&b"short extension"[..]));
+		assert_eq!(arc.name(1), Some("noext"));
+		assert_eq!(arc.get("noext"), Some(&b"no extension"[..]));
+		assert_eq!(Archives::new(tmp.path())?.index("abc.x")?, Some(0));
+		Ok(())
+	}
+
+	#[test]
+	fn missing_dir() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		let arcs = Archives::new(tmp.path().join("missing"))?;
+		assert_eq!(arcs.list().count(), 0);
+		assert_eq!(arcs.get("t0100._sn")?, None);
+		Ok(())
+	}
+
+	#[test]
+	fn cache() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		let data = tmp.path().join("data");
+		fs::create_dir(&data)?;
+		build(&data, 0, b"first")?;
+		let cachepath = tmp.path().join("index");
+
+		let arcs = Archives::new_cached(&data, &cachepath)?;
+		assert_eq!(arcs.index("t_town._dt")?, Some(2));
+		assert!(arcs.archives[&0].get().is_some());
+
+		// Only the index is read from a valid cache, so a name added to it shows up
+		let text = fs::read_to_string(&cachepath)?;
+		fs::write(&cachepath, format!("{text}name 00 extra._dt\n"))?;
+		let arcs = Archives::new_cached(&data, &cachepath)?;
+		assert!(arcs.names.contains_key("extra._dt"));
+		assert!(arcs.archives[&0].get().is_none());
+
+		// Changing only the .dat invalidates it
+		let (_, datpath) = Archive::dir_dat(&data, 0);
+		let mut dat = OpenOptions::new().append(true).open(datpath)?;
+		dat.write_all(b"more")?;
+		drop(dat);
+		let arcs = Archives::new_cached(&data, &cachepath)?;
+		assert!(!arcs.names.contains_key("extra._dt"));
+		assert_eq!(arcs.index("t_town._dt")?, Some(2));
+
+		// A broken cache is ignored
+		fs::write(&cachepath, "garbage")?;
+		let arcs = Archives::new_cached(&data, &cachepath)?;
+		assert_eq!(arcs.index("t0100._sn")?, Some(0));
+		Ok(())
+	}
+}
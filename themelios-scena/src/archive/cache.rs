@@ -0,0 +1,95 @@
+//! A persistent index of which archive each file is in, so that [`Archives`](super::Archives) can
+//! be opened without reading every .dir.
+
+use std::{
+	collections::HashMap,
+	fs,
+	io,
+	path::Path,
+	time::UNIX_EPOCH,
+};
+
+const HEADER: &str = "themelios archive index 2";
+
+/// Identifies the version of an archive's .dir and .dat files, so that stale caches can be
+/// detected.
+///
+/// Only the .dir is read when indexing, but a new .dat usually comes with a new .dir. Including
+/// both makes it much less likely to miss a change where the .dir kept its length and mtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Stamp {
+	pub num: u8,
+	pub dir: FileStamp,
+	pub dat: FileStamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FileStamp {
+	pub len: u64,
+	pub mtime: u128,
+}
+
+impl Stamp {
+	pub fn new(num: u8, dir: &Path, dat: &Path) -> io::Result<Self> {
+		Ok(Stamp { num, dir: FileStamp::new(dir)?, dat: FileStamp::new(dat)? })
+	}
+}
+
+impl FileStamp {
+	fn new(path: &Path) -> io::Result<Self> {
+		let meta = fs::metadata(path)?;
+		let mtime = meta.modified()?
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |a| a.as_nanos());
+		Ok(FileStamp { len: meta.len(), mtime })
+	}
+}
+
+/// Loads the index, if it exists and matches the given archives.
+pub(super) fn load(path: &Path, stamps: &[Stamp]) -> Option<HashMap<String, u16>> {
+	let text = fs::read_to_string(path).ok()?;
+	let mut lines = text.lines();
+	if lines.next()? != HEADER {
+		return None
+	}
+
+	let mut found = Vec::new();
+	let mut names = HashMap::new();
+	for line in lines {
+		let mut parts = line.splitn(3, ' ');
+		let kind = parts.next()?;
+		let num = u8::from_str_radix(parts.next()?, 16).ok()?;
+		let rest = parts.next()?;
+		match kind {
+			"archive" => {
+				let mut parts = rest.split(' ');
+				let mut file = || Some(FileStamp {
+					len: parts.next()?.parse().ok()?,
+					mtime: parts.next()?.parse().ok()?,
+				});
+				found.push(Stamp { num, dir: file()?, dat: file()? });
+			}
+			"name" => {
+				names.insert(rest.to_owned(), num as u16);
+			}
+			_ => return None
+		}
+	}
+
+	(found == stamps).then_some(names)
+}
+
+pub(super) fn save(path: &Path, stamps: &[Stamp], names: &HashMap<String, u16>) -> io::Result<()> {
+	let mut out = String::new();
+	out.push_str(HEADER);
+	out.push('\n');
+	for s in stamps {
+		out.push_str(&format!("archive {:02X} {} {} {} {}\n", s.num, s.dir.len, s.dir.mtime, s.dat.len, s.dat.mtime));
+	}
+	let mut names = names.iter().collect::<Vec<_>>();
+	names.sort();
+	for (name, num) in names {
+		out.push_str(&format!("name {num:02X} {name}\n"));
+	}
+	fs::write(path, out)
+}
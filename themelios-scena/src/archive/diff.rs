@@ -0,0 +1,282 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::decompress;
+use super::{Archives, Error};
+
+/// The differences between two sets of archives, such as two versions of a game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+	/// Entries only present in the second set.
+	pub added: Vec<String>,
+	/// Entries only present in the first set.
+	pub removed: Vec<String>,
+	/// Entries present in both, but with different data or metadata.
+	pub changed: Vec<Change>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+	pub name: String,
+	pub content: Content,
+	pub meta: Vec<MetaChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+	/// The data is byte-for-byte identical.
+	Same,
+	/// The compressed data differs, but decompresses to the same thing.
+	Recompressed,
+	/// The data differs. The lengths are after decompression if both are
+	/// [compressed](decompress::is_compressed) and could be decompressed, otherwise of the raw data.
+	Changed { a_len: usize, b_len: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+	/// The archive number and index, as used in file references.
+	FileRef,
+	Unk1,
+	Unk2,
+	Unk3,
+	Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetaChange {
+	pub field: Field,
+	pub a: u32,
+	pub b: u32,
+}
+
+impl Diff {
+	/// Compares all entries of the two sets of archives.
+	///
+	/// All archives are opened first, so an archive that fails to open is reported as an error
+	/// rather than as its files having been removed.
+	pub fn new(a: &Archives, b: &Archives) -> Result<Diff, Error> {
+		let names_a = names(a)?;
+		let names_b = names(b)?;
+
+		let mut diff = Diff {
+			added: names_b.difference(&names_a).map(|a| a.to_string()).collect(),
+			removed: names_a.difference(&names_b).map(|a| a.to_string()).collect(),
+			changed: Vec::new(),
+		};
+
+		for &name in names_a.intersection(&names_b) {
+			let not_found = || Error::NotFound {
+				name: name.to_owned(),
+				backtrace: std::backtrace::Backtrace::capture().into(),
+			};
+			let ea = a.entry(name)?.ok_or_else(not_found)?;
+			let eb = b.entry(name)?.ok_or_else(not_found)?;
+			let content = content(a.get(name)?.ok_or_else(not_found)?, b.get(name)?.ok_or_else(not_found)?);
+
+			let mut meta = Vec::new();
+			let mut check = |field, a: u32, b: u32| if a != b {
+				meta.push(MetaChange { field, a, b });
+			};
+			check(Field::FileRef, a.index(name)?.ok_or_else(not_found)?, b.index(name)?.ok_or_else(not_found)?);
+			check(Field::Unk1, ea.unk1, eb.unk1);
+			check(Field::Unk2, ea.unk2 as u32, eb.unk2 as u32);
+			check(Field::Unk3, ea.unk3 as u32, eb.unk3 as u32);
+			check(Field::Timestamp, ea.timestamp, eb.timestamp);
+
+			if content != Content::Same || !meta.is_empty() {
+				diff.changed.push(Change {
+					name: name.to_owned(),
+					content,
+					meta,
+				});
+			}
+		}
+
+		Ok(diff)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+/// All entry names, including empty files, which [`Archives::list`] leaves out.
+fn names(arcs: &Archives) -> Result<BTreeSet<&str>, Error> {
+	Ok(arcs.archives()?
+		.flat_map(|a| a.1.entries())
+		.map(|a| a.name.as_str())
+		.filter(|&a| a != "/_______.___")
+		.collect())
+}
+
+fn content(a: &[u8], b: &[u8]) -> Content {
+	if a == b {
+		return Content::Same
+	}
+	if !decompress::is_compressed(a) || !decompress::is_compressed(b) {
+		return Content::Changed { a_len: a.len(), b_len: b.len() }
+	}
+	if let (Ok(a), Ok(b)) = (decompress::decompress(a), decompress::decompress(b)) {
+		if a == b {
+			return Content::Recompressed
+		}
+		return Content::Changed { a_len: a.len(), b_len: b.len() }
+	}
+	Content::Changed { a_len: a.len(), b_len: b.len() }
+}
+
+impl fmt::Display for Diff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for name in &self.removed {
+			writeln!(f, "- {name}")?;
+		}
+		for name in &self.added {
+			writeln!(f, "+ {name}")?;
+		}
+		for change in &self.changed {
+			writeln!(f, "{change}")?;
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for Change {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "~ {}", self.name)?;
+		match self.content {
+			Content::Same => {}
+			Content::Recompressed => write!(f, " recompressed")?,
+			Content::Changed { a_len, b_len } => write!(f, " changed ({a_len} -> {b_len} bytes)")?,
+		}
+		for m in &self.meta {
+			write!(f, ", {m}")?;
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for MetaChange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let name = match self.field {
+			Field::FileRef => "fileref",
+			Field::Unk1 => "unk1",
+			Field::Unk2 => "unk2",
+			Field::Unk3 => "unk3",
+			Field::Timestamp => "timestamp",
+		};
+		write!(f, "{name} 0x{:08X} -> 0x{:08X}", self.a, self.b)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::archive::{Archive, ArchiveBuilder, BuildEntry};
+	use crate::compress::{self, Mode};
+
+	#[test]
+	fn diff() -> Result<(), Error> {
+		let text = b"some text that compresses, some text that compresses".as_slice();
+		let tmp_a = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("same._dt", b"same".as_slice(), 1));
+		b.push(BuildEntry::new("changed._dt", b"old".as_slice(), 1));
+		b.push(BuildEntry::new("recomp._dt", compress::compress(text, Mode::Bits, 6), 1));
+		b.push(BuildEntry::new("removed._dt", b"gone".as_slice(), 1));
+		b.push(BuildEntry::new("emptied._dt", b"data".as_slice(), 1));
+		b.push(BuildEntry::new("empty._dt", b"".as_slice(), 1));
+		b.save(tmp_a.path(), 1)?;
+
+		let tmp_b = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("same._dt", b"same".as_slice(), 1));
+		b.push(BuildEntry::new("changed._dt", b"new data".as_slice(), 2));
+		b.push(BuildEntry::new("recomp._dt", compress::compress(text, Mode::Bytes, 6), 1));
+		b.push_placeholder();
+		b.push(BuildEntry::new("emptied._dt", b"".as_slice(), 1));
+		b.push(BuildEntry::new("empty._dt", b"".as_slice(), 1));
+		b.push(BuildEntry::new("added._dt", b"new".as_slice(), 1));
+		b.push(BuildEntry::new("added_empty._dt", b"".as_slice(), 1));
+		b.save(tmp_b.path(), 1)?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("moved._dt", b"moved".as_slice(), 1));
+		b.save(tmp_a.path(), 2)?;
+		let mut b = ArchiveBuilder::new();
+		b.push_placeholder();
+		b.push(BuildEntry::new("moved._dt", b"moved".as_slice(), 1));
+		b.save(tmp_b.path(), 2)?;
+
+		let diff = Diff::new(&Archives::new(tmp_a.path())?, &Archives::new(tmp_b.path())?)?;
+		assert_eq!(diff.added, ["added._dt", "added_empty._dt"]);
+		assert_eq!(diff.removed, ["removed._dt"]);
+		assert_eq!(diff.changed, [
+			Change {
+				name: "changed._dt".to_owned(),
+				content: Content::Changed { a_len: 3, b_len: 8 },
+				meta: vec![MetaChange { field: Field::Timestamp, a: 1, b: 2 }],
+			},
+			Change {
+				name: "emptied._dt".to_owned(),
+				content: Content::Changed { a_len: 4, b_len: 0 },
+				meta: vec![],
+			},
+			Change {
+				name: "moved._dt".to_owned(),
+				content: Content::Same,
+				meta: vec![MetaChange { field: Field::FileRef, a: 0x0002_0000, b: 0x0002_0001 }],
+			},
+			Change {
+				name: "recomp._dt".to_owned(),
+				content: Content::Recompressed,
+				meta: vec![],
+			},
+		]);
+		assert_eq!(Diff::new(&Archives::new(tmp_a.path())?, &Archives::new(tmp_a.path())?)?, Diff::default());
+		Ok(())
+	}
+
+	#[test]
+	fn diff_uncompressed() -> Result<(), Error> {
+		// Uncompressed data that starts with a chunk stream, which decompresses fine but ignores the
+		// trailing bytes
+		let stream = compress::compress(b"some text", Mode::Bits, 6);
+		let a = [stream.as_slice(), b"first"].concat();
+		let b = [stream.as_slice(), b"second"].concat();
+		assert!(decompress::decompress(&a).is_ok());
+		assert!(!decompress::is_compressed(&a));
+		assert_eq!(content(&a, &b), Content::Changed { a_len: a.len(), b_len: b.len() });
+		assert_eq!(content(&a, &stream), Content::Changed { a_len: a.len(), b_len: stream.len() });
+
+		let tmp_a = tempfile::tempdir()?;
+		let mut builder = ArchiveBuilder::new();
+		builder.push(BuildEntry::new("file._dt", a.clone(), 1));
+		builder.save(tmp_a.path(), 1)?;
+		let tmp_b = tempfile::tempdir()?;
+		let mut builder = ArchiveBuilder::new();
+		builder.push(BuildEntry::new("file._dt", b.clone(), 1));
+		builder.save(tmp_b.path(), 1)?;
+		let diff = Diff::new(&Archives::new(tmp_a.path())?, &Archives::new(tmp_b.path())?)?;
+		assert_eq!(diff.changed, [Change {
+			name: "file._dt".to_owned(),
+			content: Content::Changed { a_len: a.len(), b_len: b.len() },
+			meta: vec![],
+		}]);
+		Ok(())
+	}
+
+	#[test]
+	fn diff_broken() -> Result<(), Error> {
+		let tmp_a = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("file._dt", b"data".as_slice(), 1));
+		b.save(tmp_a.path(), 1)?;
+		let tmp_b = tempfile::tempdir()?;
+		b.save(tmp_b.path(), 1)?;
+		let (_, dat) = Archive::dir_dat(tmp_b.path(), 1);
+		std::fs::write(dat, b"not an archive")?;
+
+		let result = Diff::new(&Archives::new(tmp_a.path())?, &Archives::new(tmp_b.path())?);
+		assert!(result.is_err(), "{result:?}");
+		Ok(())
+	}
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, fmt, ops::Range};
+
+use crate::util;
+use super::{Entry, HEADER_SIZE, DIR_RECORD_SIZE, dir_record, dat_offset};
+
+/// Something wrong with an archive.
+///
+/// Problems that are [fatal](Problem::is_fatal) make [`Archive::from_dir_dat`](super::Archive::from_dir_dat)
+/// fail, but [`Archive::from_dir_dat_lenient`](super::Archive::from_dir_dat_lenient) will still make
+/// a best effort at reading the archive.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Problem {
+	#[error("bad magic in .{file}")]
+	Magic { file: &'static str },
+
+	#[error("dir has room for {fits} entries, but claims {count}")]
+	Truncated { count: u64, fits: u64 },
+
+	#[error("dir has {len} bytes of trailing data")]
+	Trailing { len: usize },
+
+	#[error("dir has {dir} entries, but dat has {dat}")]
+	Count { dir: u64, dat: u64 },
+
+	#[error("dat data should start at {expected}, but header says {found}")]
+	DataStart { expected: u64, found: u32 },
+
+	#[error("entry {index} has undecodable name {raw:02X?}")]
+	Name { index: usize, raw: Vec<u8> },
+
+	#[error("entries {a} and {b} are both named {name:?}")]
+	Duplicate { name: String, a: usize, b: usize },
+
+	#[error("entry {index} at {range:?} is outside the dat (length {dat_len})")]
+	OutOfRange { index: usize, range: Range<u64>, dat_len: usize },
+
+	#[error("entry {index} ends at {expected}, but dat says {found:?}")]
+	End { index: usize, expected: u64, found: Option<u32> },
+
+	#[error("entries {a} and {b} overlap")]
+	Overlap { a: usize, b: usize },
+
+	#[error("unused data at {range:?}")]
+	Gap { range: Range<usize> },
+}
+
+impl Problem {
+	/// Whether this problem makes [`Archive::from_dir_dat`](super::Archive::from_dir_dat) reject
+	/// the archive.
+	///
+	/// Only duplicate names are allowed, since the later entry simply shadows the earlier one.
+	/// Overlaps and gaps are fatal, even though tools that patch archives in place (including
+	/// [`Archive::replace`](super::Archive::replace)) leave gaps behind; such archives need to be
+	/// opened with [`Archive::from_dir_dat_lenient`](super::Archive::from_dir_dat_lenient).
+	pub fn is_fatal(&self) -> bool {
+		!matches!(self, Problem::Duplicate { .. })
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+	pub problems: Vec<Problem>,
+}
+
+impl Report {
+	/// Whether the archive has no fatal problems.
+	pub fn is_ok(&self) -> bool {
+		!self.problems.iter().any(Problem::is_fatal)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.problems.is_empty()
+	}
+
+	pub fn fatal(&self) -> impl Iterator<Item=&Problem> {
+		self.problems.iter().filter(|a| a.is_fatal())
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for p in &self.problems {
+			writeln!(f, "{}{p}", if p.is_fatal() { "error: " } else { "warning: " })?;
+		}
+		Ok(())
+	}
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+	Some(u32::from_le_bytes(data.get(pos..pos+4)?.try_into().unwrap()))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Option<u64> {
+	Some(u64::from_le_bytes(data.get(pos..pos+8)?.try_into().unwrap()))
+}
+
+/// Turns an on-disk name like `T0100   ._SN` into `t0100._sn`.
+///
+/// Extensions shorter than three characters are padded too, so `ABC     .X  ` becomes `abc.x`.
+fn normalize_name(name: String) -> String {
+	let name = if let Some((name, ext)) = name.split_once('.') {
+		format!("{}.{}", name.trim_end(), ext.trim_end())
+	} else {
+		name.trim_end().to_owned()
+	};
+	name.to_lowercase()
+}
+
+/// Reads only the entry names from a .dir, without checking anything.
+pub(super) fn names(dir: &[u8]) -> Vec<String> {
+	let count = u64_at(dir, 8).unwrap_or(0).min((dir.len().saturating_sub(HEADER_SIZE) / DIR_RECORD_SIZE) as u64);
+	(0..count as usize).map(|index| {
+		normalize_name(cp932::decode_lossy(&dir[dir_record(index)..][..12]))
+	}).collect()
+}
+
+/// Reads as much as possible of the archive, noting anything wrong with it.
+pub(super) fn read(dir: &[u8], dat: &[u8]) -> (HashMap<String, usize>, Vec<Entry>, Report) {
+	let mut report = Report::default();
+	let mut names = HashMap::new();
+	let mut entries = Vec::new();
+
+	if !dir.starts_with(b"LB DIR\x1A\0") {
+		report.problems.push(Problem::Magic { file: "dir" });
+	}
+	if !dat.starts_with(b"LB DAT\x1A\0") {
+		report.problems.push(Problem::Magic { file: "dat" });
+	}
+
+	let mut count = u64_at(dir, 8).unwrap_or(0);
+	let fits = (dir.len().saturating_sub(HEADER_SIZE) / DIR_RECORD_SIZE) as u64;
+	if count > fits {
+		report.problems.push(Problem::Truncated { count, fits });
+		count = fits;
+	}
+	let end = dir_record(count as usize);
+	if dir.len() > end {
+		report.problems.push(Problem::Trailing { len: dir.len() - end });
+	}
+
+	let dat_count = u64_at(dat, 8).unwrap_or(0);
+	if dat_count != count {
+		report.problems.push(Problem::Count { dir: count, dat: dat_count });
+	}
+	let data_start = dat_offset(count as usize + 1) as u64;
+	let found = u32_at(dat, dat_offset(0)).unwrap_or(0);
+	if found as u64 != data_start {
+		report.problems.push(Problem::DataStart { expected: data_start, found });
+	}
+
+	for index in 0..count as usize {
+		let rec = &dir[dir_record(index)..][..DIR_RECORD_SIZE];
+		let raw = &rec[..12];
+		let name = match util::decode(raw) {
+			Ok(name) => name,
+			Err(_) => {
+				report.problems.push(Problem::Name { index, raw: raw.to_owned() });
+				cp932::decode_lossy(raw)
+			}
+		};
+		let name = normalize_name(name);
+
+		let unk1 = u32_at(rec, 12).unwrap(); // Zero in all but a few files in 3rd; in those cases it looks kinda like a timestamp
+		let unk2 = u32_at(rec, 16).unwrap() as usize;
+		let unk3 = u32_at(rec, 20).unwrap() as usize;
+		let len = u32_at(rec, 24).unwrap();
+		let timestamp = u32_at(rec, 28).unwrap();
+		let offset = u32_at(rec, 32).unwrap();
+
+		// Done in u64 so that entries past the 4GiB mark are caught, even on 32-bit targets
+		let end = offset as u64 + len as u64;
+		let range = if end > dat.len() as u64 {
+			report.problems.push(Problem::OutOfRange { index, range: offset as u64..end, dat_len: dat.len() });
+			(offset as usize).min(dat.len())..dat.len()
+		} else {
+			offset as usize..end as usize
+		};
+
+		let found = u32_at(dat, dat_offset(index + 1));
+		if found.map(u64::from) != Some(end) {
+			report.problems.push(Problem::End { index, expected: end, found });
+		}
+
+		if name != "/_______.___" {
+			if let Some(prev) = names.insert(name.clone(), index) {
+				report.problems.push(Problem::Duplicate { name: name.clone(), a: prev, b: index });
+			}
+		}
+
+		entries.push(Entry {
+			index,
+			name,
+			unk1,
+			unk2,
+			unk3,
+			timestamp,
+			range,
+		});
+	}
+
+	let mut ranges = entries.iter()
+		.filter(|a| !a.is_empty())
+		.map(|a| (a.range.clone(), a.index))
+		.collect::<Vec<_>>();
+	ranges.sort_by_key(|a| (a.0.start, a.0.end));
+	let mut pos = (data_start as usize).min(dat.len());
+	let mut last: Option<(usize, usize)> = None;
+	for (range, index) in ranges {
+		if range.start > pos {
+			report.problems.push(Problem::Gap { range: pos..range.start });
+		}
+		if let Some((end, prev)) = last && range.start < end {
+			report.problems.push(Problem::Overlap { a: prev, b: index });
+		}
+		if last.map_or(true, |(end, _)| range.end > end) {
+			last = Some((range.end, index));
+		}
+		pos = pos.max(range.end);
+	}
+	if pos < dat.len() {
+		report.problems.push(Problem::Gap { range: pos..dat.len() });
+	}
+
+	(names, entries, report)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::archive::{ArchiveBuilder, BuildEntry};
+
+	// Two entries, with data at 28..32 and 32..37
+	fn archive() -> (Vec<u8>, Vec<u8>) {
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("a._dt", b"aaaa".as_slice(), 0));
+		b.push(BuildEntry::new("b._dt", b"bbbbb".as_slice(), 0));
+		let (mut dir, mut dat) = (Vec::new(), Vec::new());
+		b.write(&mut dir, &mut dat).unwrap();
+		(dir, dat)
+	}
+
+	fn set_u32(data: &mut [u8], pos: usize, v: u32) {
+		data[pos..pos+4].copy_from_slice(&v.to_le_bytes());
+	}
+
+	fn problems(dir: &[u8], dat: &[u8]) -> Vec<Problem> {
+		read(dir, dat).2.problems
+	}
+
+	#[test]
+	fn valid() {
+		let (dir, dat) = archive();
+		assert_eq!(problems(&dir, &dat), []);
+	}
+
+	#[test]
+	fn magic() {
+		let (dir, mut dat) = archive();
+		dat[0] = b'X';
+		assert_eq!(problems(&dir, &dat), [Problem::Magic { file: "dat" }]);
+	}
+
+	#[test]
+	fn truncated() {
+		let (dir, dat) = archive();
+		let dir = &dir[..dir.len() - 1];
+		assert_eq!(problems(dir, &dat)[..2], [
+			Problem::Truncated { count: 2, fits: 1 },
+			Problem::Trailing { len: 35 },
+		]);
+	}
+
+	#[test]
+	fn count() {
+		let (dir, mut dat) = archive();
+		dat[8] = 3;
+		assert_eq!(problems(&dir, &dat), [Problem::Count { dir: 2, dat: 3 }]);
+	}
+
+	#[test]
+	fn data_start() {
+		let (dir, mut dat) = archive();
+		set_u32(&mut dat, dat_offset(0), 24);
+		assert_eq!(problems(&dir, &dat), [Problem::DataStart { expected: 28, found: 24 }]);
+	}
+
+	#[test]
+	fn name() {
+		let (mut dir, dat) = archive();
+		// 0x81 is a lead byte, but 0x20 is not a valid trail byte
+		dir[dir_record(1)] = 0x81;
+		dir[dir_record(1) + 1] = b' ';
+		assert_eq!(problems(&dir, &dat), [Problem::Name { index: 1, raw: b"\x81       ._DT".to_vec() }]);
+	}
+
+	#[test]
+	fn duplicate() {
+		let (mut dir, dat) = archive();
+		dir[dir_record(1)] = b'A';
+		assert_eq!(problems(&dir, &dat), [Problem::Duplicate { name: "a._dt".to_owned(), a: 0, b: 1 }]);
+	}
+
+	#[test]
+	fn out_of_range() {
+		let (mut dir, mut dat) = archive();
+		set_u32(&mut dir, dir_record(1) + 24, 10);
+		set_u32(&mut dat, dat_offset(2), 42);
+		let (_, entries, report) = read(&dir, &dat);
+		assert_eq!(report.problems, [Problem::OutOfRange { index: 1, range: 32..42, dat_len: 37 }]);
+		assert_eq!(entries[1].range, 32..37);
+	}
+
+	#[test]
+	fn out_of_range_overflow() {
+		// The end wraps around to 0x10 in 32 bits, which must not be mistaken for the end in the dat
+		let (mut dir, mut dat) = archive();
+		set_u32(&mut dir, dir_record(1) + 24, 0x20);
+		set_u32(&mut dir, dir_record(1) + 32, 0xFFFF_FFF0);
+		set_u32(&mut dat, dat_offset(2), 0x10);
+		assert_eq!(problems(&dir, &dat)[..2], [
+			Problem::OutOfRange { index: 1, range: 0xFFFF_FFF0..0x1_0000_0010, dat_len: 37 },
+			Problem::End { index: 1, expected: 0x1_0000_0010, found: Some(0x10) },
+		]);
+	}
+
+	#[test]
+	fn end() {
+		let (dir, mut dat) = archive();
+		set_u32(&mut dat, dat_offset(1), 31);
+		assert_eq!(problems(&dir, &dat), [Problem::End { index: 0, expected: 32, found: Some(31) }]);
+	}
+
+	#[test]
+	fn overlap() {
+		let (mut dir, mut dat) = archive();
+		set_u32(&mut dir, dir_record(1) + 32, 30);
+		set_u32(&mut dat, dat_offset(2), 35);
+		assert_eq!(problems(&dir, &dat), [
+			Problem::Overlap { a: 0, b: 1 },
+			Problem::Gap { range: 35..37 },
+		]);
+	}
+
+	#[test]
+	fn gap() {
+		let (dir, mut dat) = archive();
+		dat.extend(b"junk");
+		assert_eq!(problems(&dir, &dat), [Problem::Gap { range: 37..41 }]);
+	}
+
+	#[test]
+	fn empty() {
+		assert_eq!(problems(&[], &[]), [
+			Problem::Magic { file: "dir" },
+			Problem::Magic { file: "dat" },
+			Problem::DataStart { expected: 20, found: 0 },
+		]);
+	}
+}
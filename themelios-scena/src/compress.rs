@@ -0,0 +1,463 @@
+/// Maximum number of uncompressed bytes in a chunk.
+pub const CHUNK_SIZE: usize = 0xFFF0;
+/// Maximum size of a compressed chunk, so that its length including the length field fits in a u16.
+const MAX_CHUNK: usize = 0xFFFF - 2;
+
+/// A reasonable tradeoff between speed and compression ratio.
+pub const DEFAULT_LEVEL: u8 = 6;
+pub const MAX_LEVEL: u8 = 10;
+
+const WINDOW: usize = 0x1FFF;
+
+const MAX_COUNT: usize = 269;
+const MIN_RUN: usize = 14;
+const MAX_RUN: usize = 14 + 0xFFF;
+
+const MIN_BACKREF2: usize = 4;
+const MAX_BACKREF2: usize = 7;
+const MAX_REPEAT2: usize = 31;
+const MIN_RUN2: usize = 4;
+const MAX_RUN2: usize = 4 + 0xFFF;
+const MAX_VERBATIM2: usize = 0x1FFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+	/// The bit-based format, used by most ED6 files.
+	#[default]
+	Bits,
+	/// The byte-based format, used by ED7.
+	Bytes,
+}
+
+struct BitWriter {
+	out: Vec<u8>,
+	word: usize,
+	nextbit: u16,
+}
+
+impl BitWriter {
+	fn new() -> Self {
+		// The first bit word's low byte is the zero that marks this as mode 1.
+		BitWriter {
+			out: vec![0, 0],
+			word: 0,
+			nextbit: 1 << 8,
+		}
+	}
+
+	fn u8(&mut self, b: u8) {
+		self.out.push(b);
+	}
+
+	fn bit(&mut self, v: bool) {
+		if self.nextbit == 0 {
+			self.word = self.out.len();
+			self.out.extend([0, 0]);
+			self.nextbit = 1;
+		}
+		if v {
+			let bits = u16::from_le_bytes([self.out[self.word], self.out[self.word+1]]) | self.nextbit;
+			self.out[self.word..self.word+2].copy_from_slice(&bits.to_le_bytes());
+		}
+		self.nextbit <<= 1;
+	}
+
+	fn bits(&mut self, n: usize, x: usize) {
+		for i in (0..n%8).rev() {
+			self.bit(x >> (n/8*8 + i) & 1 != 0);
+		}
+		for i in (0..n/8).rev() {
+			self.u8((x >> (i*8)) as u8);
+		}
+	}
+
+	fn count(&mut self, n: usize) {
+		match n {
+			2..=5 => {
+				for _ in 2..n {
+					self.bit(false);
+				}
+				self.bit(true);
+			}
+			6..=13 => {
+				for _ in 0..4 {
+					self.bit(false);
+				}
+				self.bit(true);
+				self.bits(3, n - 6);
+			}
+			14..=MAX_COUNT => {
+				for _ in 0..5 {
+					self.bit(false);
+				}
+				self.bits(8, n - 14);
+			}
+			_ => unreachable!(),
+		}
+	}
+
+	fn verbatim(&mut self, b: u8) {
+		self.bit(false);
+		self.u8(b);
+	}
+
+	fn repeat(&mut self, n: usize, o: usize) {
+		self.bit(true);
+		if o < 0x100 {
+			self.bit(false);
+			self.bits(8, o);
+		} else {
+			self.bit(true);
+			self.bits(13, o);
+		}
+		self.count(n);
+	}
+
+	fn constant(&mut self, n: usize, b: u8) {
+		self.bit(true);
+		self.bit(true);
+		self.bits(13, 1);
+		let n = n - MIN_RUN;
+		if n >= 0x10 {
+			self.bit(true);
+			self.bits(12, n);
+		} else {
+			self.bit(false);
+			self.bits(4, n);
+		}
+		self.u8(b);
+	}
+
+	fn end(mut self) -> Vec<u8> {
+		self.bit(true);
+		self.bit(true);
+		self.bits(13, 0);
+		self.out
+	}
+}
+
+/// Finds earlier occurrences of the data at each position, by chaining together all positions
+/// that start with the same two bytes.
+///
+/// Higher levels search further back along the chain, which is slower but finds better matches.
+struct Matcher<'a> {
+	data: &'a [u8],
+	head: Vec<usize>,
+	prev: Vec<usize>,
+	chain: usize,
+}
+
+impl<'a> Matcher<'a> {
+	fn new(data: &'a [u8], level: u8) -> Self {
+		Matcher {
+			data,
+			head: vec![usize::MAX; 0x10000],
+			prev: vec![usize::MAX; data.len()],
+			chain: 4 << level.min(MAX_LEVEL),
+		}
+	}
+
+	fn key(&self, pos: usize) -> Option<usize> {
+		let a = *self.data.get(pos)? as usize;
+		let b = *self.data.get(pos+1)? as usize;
+		Some(a << 8 | b)
+	}
+
+	fn insert(&mut self, pos: usize) {
+		if let Some(key) = self.key(pos) {
+			self.prev[pos] = self.head[key];
+			self.head[key] = pos;
+		}
+	}
+
+	/// Returns the longest match as `(length, offset)`, preferring the closest one.
+	fn find(&self, pos: usize, max: usize) -> Option<(usize, usize)> {
+		let key = self.key(pos)?;
+		let max = (self.data.len() - pos).min(max);
+		let mut best = None;
+		let mut cand = self.head[key];
+		for _ in 0..self.chain {
+			if cand == usize::MAX || pos - cand > WINDOW {
+				break
+			}
+			let len = self.data[cand..].iter().zip(&self.data[pos..pos+max])
+				.take_while(|(a, b)| a == b)
+				.count();
+			if len > best.map_or(1, |a: (usize, usize)| a.0) {
+				best = Some((len, pos - cand));
+				if len == max {
+					break
+				}
+			}
+			cand = self.prev[cand];
+		}
+		best
+	}
+
+	/// Returns the length of the match at the given offset.
+	fn len_at(&self, pos: usize, o: usize, max: usize) -> usize {
+		if !(1..=pos).contains(&o) {
+			return 0
+		}
+		self.data[pos-o..].iter().zip(&self.data[pos..]).take(max)
+			.take_while(|(a, b)| a == b)
+			.count()
+	}
+
+	fn insert_all(&mut self, range: std::ops::Range<usize>) {
+		for p in range {
+			self.insert(p);
+		}
+	}
+}
+
+fn run_len(data: &[u8], max: usize) -> usize {
+	data.iter().take(max).take_while(|a| **a == data[0]).count()
+}
+
+fn compress1(data: &[u8], level: u8) -> Vec<u8> {
+	let mut w = BitWriter::new();
+	let mut m = Matcher::new(data, level);
+	let mut pos = 0;
+	while pos < data.len() {
+		let run = run_len(&data[pos..], MAX_RUN);
+		let n = if run >= MIN_RUN {
+			w.constant(run, data[pos]);
+			run
+		} else if let Some((n, o)) = m.find(pos, MAX_COUNT) {
+			w.repeat(n, o);
+			n
+		} else {
+			w.verbatim(data[pos]);
+			1
+		};
+		m.insert_all(pos..pos+n);
+		pos += n;
+	}
+	w.end()
+}
+
+fn compress2(data: &[u8], level: u8) -> Vec<u8> {
+	fn verbatim(out: &mut Vec<u8>, data: &[u8]) {
+		for data in data.chunks(MAX_VERBATIM2) {
+			let n = data.len();
+			if n < 0x20 {
+				out.push(n as u8);
+			} else {
+				out.extend([0x20 | (n >> 8) as u8, n as u8]);
+			}
+			out.extend(data);
+		}
+	}
+
+	let mut out = Vec::new();
+	let mut m = Matcher::new(data, level);
+	let mut last_o = 0;
+	let mut start = 0;
+	let mut pos = 0;
+	while pos < data.len() {
+		let run = run_len(&data[pos..], MAX_RUN2);
+		let last = m.len_at(pos, last_o, MAX_REPEAT2);
+		let best = m.find(pos, MAX_COUNT).filter(|a| a.0 >= MIN_BACKREF2);
+
+		let n = if run >= MIN_RUN2 && run >= best.map_or(0, |a| a.0) {
+			verbatim(&mut out, &data[start..pos]);
+			let n = run - MIN_RUN2;
+			if n < 0x10 {
+				out.extend([0x40 | n as u8, data[pos]]);
+			} else {
+				out.extend([0x50 | (n >> 8) as u8, n as u8, data[pos]]);
+			}
+			run
+		} else if last >= 2 && best.map_or(true, |a| last >= a.0.min(MAX_BACKREF2)) {
+			verbatim(&mut out, &data[start..pos]);
+			out.push(0x60 | last as u8);
+			last
+		} else if let Some((n, o)) = best {
+			// Longer matches are continued with repeat-last on the next iterations
+			verbatim(&mut out, &data[start..pos]);
+			let n = n.min(MAX_BACKREF2);
+			out.extend([0x80 | ((n - MIN_BACKREF2) << 5 | o >> 8) as u8, o as u8]);
+			last_o = o;
+			n
+		} else {
+			m.insert(pos);
+			pos += 1;
+			continue
+		};
+
+		m.insert_all(pos..pos+n);
+		pos += n;
+		start = pos;
+	}
+	verbatim(&mut out, &data[start..pos]);
+	out
+}
+
+/// Compresses a single chunk.
+///
+/// In [`Mode::Bits`], incompressible data may give a result that is too large for a chunk stream;
+/// [`compress`] deals with that by using smaller chunks.
+pub fn compress_chunk(data: &[u8], mode: Mode, level: u8) -> Vec<u8> {
+	match mode {
+		Mode::Bits => compress1(data, level),
+		Mode::Bytes => compress2(data, level),
+	}
+}
+
+/// Compresses data into a chunk stream, as read by [`decompress`](crate::decompress::decompress).
+///
+/// `level` ranges from 0 to [`MAX_LEVEL`], trading speed for compression ratio.
+pub fn compress(data: &[u8], mode: Mode, level: u8) -> Vec<u8> {
+	stream(&chunks(data, mode, level))
+}
+
+/// Splits data into chunks and compresses each of them.
+fn chunks(data: &[u8], mode: Mode, level: u8) -> Vec<Vec<u8>> {
+	let mut chunks = Vec::new();
+	let mut pos = 0;
+	loop {
+		let mut len = (data.len() - pos).min(CHUNK_SIZE);
+		let chunk = loop {
+			let chunk = compress_chunk(&data[pos..pos+len], mode, level);
+			if chunk.len() <= MAX_CHUNK {
+				break chunk
+			}
+			len /= 2;
+		};
+		pos += len;
+		chunks.push(chunk);
+		if pos == data.len() {
+			break
+		}
+	}
+	chunks
+}
+
+/// Joins compressed chunks into a chunk stream.
+fn stream(chunks: &[impl AsRef<[u8]>]) -> Vec<u8> {
+	let mut out = Vec::new();
+	for (i, chunk) in chunks.iter().enumerate() {
+		let chunk = chunk.as_ref();
+		out.extend(u16::to_le_bytes((chunk.len() + 2) as u16));
+		out.extend(chunk);
+		out.push(u8::from(i + 1 < chunks.len()));
+	}
+	out
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::decompress;
+
+	/// Bytes without any structure, so that matches are rare and short.
+	fn noise(len: usize) -> Vec<u8> {
+		let mut x = 0x2545F491u32;
+		(0..len).map(|_| {
+			x ^= x << 13;
+			x ^= x >> 17;
+			x ^= x << 5;
+			(x >> 24) as u8
+		}).collect()
+	}
+
+	fn roundtrip(data: &[u8], mode: Mode) -> Vec<u8> {
+		for level in [0, DEFAULT_LEVEL, MAX_LEVEL] {
+			let out = compress(data, mode, level);
+			assert_eq!(decompress::decompress(&out).unwrap(), data, "{mode:?} level {level}");
+		}
+		compress(data, mode, DEFAULT_LEVEL)
+	}
+
+	#[test]
+	fn bits_short() {
+		for data in [&b""[..], b"a", b"ab", b"aaa", b"abab"] {
+			roundtrip(data, Mode::Bits);
+		}
+	}
+
+	#[test]
+	fn bits_runs() {
+		// The shortest run, the longest with a 4-bit count, the shortest with a 12-bit count, and the
+		// longest, which is followed by another run
+		for n in [MIN_RUN, MIN_RUN + 0xF, MIN_RUN + 0x10, MAX_RUN, MAX_RUN + 1, 5000] {
+			roundtrip(&vec![7; n], Mode::Bits);
+		}
+		let out = roundtrip(&[7; 1000], Mode::Bits);
+		assert!(out.len() < 16, "{out:02X?}");
+
+		let mut data = noise(100);
+		data.extend([0; 20]);
+		data.extend(noise(100));
+		roundtrip(&data, Mode::Bits);
+	}
+
+	#[test]
+	fn bits_far() {
+		// Repeats at offsets that need the 13-bit form, up to the edge of the window
+		for dist in [0x100, 0x1000, WINDOW] {
+			let mut data = noise(dist);
+			data.extend_from_within(..200);
+			let out = roundtrip(&data, Mode::Bits);
+			assert!(out.len() < dist * 9 / 8 + 32, "{dist:#X}: {} bytes", out.len());
+		}
+	}
+
+	#[test]
+	fn bits_chunks() {
+		for (len, n) in [(CHUNK_SIZE, 1), (CHUNK_SIZE + 1, 2), (CHUNK_SIZE * 2, 2)] {
+			let out = roundtrip(&vec![1; len], Mode::Bits);
+			let chunks = decompress::chunks(&out).unwrap();
+			assert_eq!(chunks.len(), n, "{len:#X}");
+			assert_eq!(decompress::decompress_chunk(chunks[0]).unwrap().len(), CHUNK_SIZE.min(len));
+		}
+
+		// Incompressible data takes nine bits per byte, so full chunks would be too large
+		let out = roundtrip(&noise(CHUNK_SIZE), Mode::Bits);
+		let chunks = decompress::chunks(&out).unwrap();
+		assert!(chunks.len() > 1);
+		assert!(chunks.iter().all(|a| a.len() <= MAX_CHUNK));
+	}
+
+	#[test]
+	fn bytes_constant() {
+		// 010x: the count is stored minus four, in four bits or twelve
+		assert_eq!(compress_chunk(&[5; 10], Mode::Bytes, DEFAULT_LEVEL), [0x46, 5]);
+		assert_eq!(compress_chunk(&[5; 100], Mode::Bytes, DEFAULT_LEVEL), [0x50, 96, 5]);
+		for n in [MIN_RUN2, MIN_RUN2 + 0xF, MIN_RUN2 + 0x10, MAX_RUN2, MAX_RUN2 + 1] {
+			roundtrip(&vec![5; n], Mode::Bytes);
+		}
+	}
+
+	#[test]
+	fn bytes_backref() {
+		// A verbatim block, a 1nnooooo backref of the maximum length, and 011 repeating the last
+		// offset for the rest of the match
+		let data = b"abcdefghijabcdefghij";
+		let mut expected = vec![10];
+		expected.extend(b"abcdefghij");
+		expected.extend([0x80 | (MAX_BACKREF2 - MIN_BACKREF2) as u8 * 0x20, 10, 0x60 | 3]);
+		let out = compress_chunk(data, Mode::Bytes, DEFAULT_LEVEL);
+		assert_eq!(out, expected);
+		assert_eq!(decompress::decompress_chunk(&out).unwrap(), data);
+
+		for dist in [0x100, WINDOW] {
+			let mut data = noise(dist);
+			data.extend_from_within(..200);
+			let out = roundtrip(&data, Mode::Bytes);
+			assert!(out.len() < dist + 100, "{dist:#X}: {} bytes", out.len());
+		}
+	}
+
+	#[test]
+	fn bytes_chunks() {
+		roundtrip(b"", Mode::Bytes);
+		let mut data = noise(CHUNK_SIZE * 3 + 5);
+		data[1000..2000].fill(0);
+		let out = roundtrip(&data, Mode::Bytes);
+		let chunks = decompress::chunks(&out).unwrap();
+		assert_eq!(chunks.len(), 4);
+		assert_eq!(decompress::decompress_chunk(chunks[3]).unwrap(), &data[CHUNK_SIZE * 3..]);
+	}
+}
@@ -1,4 +1,4 @@
-use std::io::{Read, Result, Error, ErrorKind};
+use std::io::{Read, Write, Result, Error, ErrorKind};
 
 macro_rules! ioe {
 	($kind:ident, $e1:literal, $($args:tt)*) => {
@@ -15,19 +15,83 @@ fn u8(data: &mut impl Read) -> Result<u8> {
 	Ok(buf[0])
 }
 
-struct Ctx<'b> {
-	out: Vec<u8>,
+/// Where decompressed data is written.
+///
+/// Lengths and offsets are relative to the start of the current chunk.
+trait Out {
+	fn len(&self) -> usize;
+	fn push(&mut self, b: u8);
+	fn repeat(&mut self, n: usize, o: usize);
+}
+
+/// Appends to a vec, which may already contain earlier chunks.
+struct VecOut<'a> {
+	out: &'a mut Vec<u8>,
+	start: usize,
+}
+
+impl<'a> VecOut<'a> {
+	fn new(out: &'a mut Vec<u8>) -> Self {
+		let start = out.len();
+		VecOut { out, start }
+	}
+}
+
+impl Out for VecOut<'_> {
+	fn len(&self) -> usize {
+		self.out.len() - self.start
+	}
+
+	fn push(&mut self, b: u8) {
+		self.out.push(b);
+	}
+
+	fn repeat(&mut self, n: usize, o: usize) {
+		for _ in 0..n {
+			self.out.push(self.out[self.out.len()-o]);
+		}
+	}
+}
+
+/// Only counts the output length, for [`decompressed_size`].
+struct CountOut(usize);
+
+impl Out for CountOut {
+	fn len(&self) -> usize {
+		self.0
+	}
+
+	fn push(&mut self, _: u8) {
+		self.0 += 1;
+	}
+
+	fn repeat(&mut self, n: usize, _: usize) {
+		self.0 += n;
+	}
+}
+
+struct Ctx<'b, O> {
+	out: O,
 	data: &'b [u8],
 	pos: usize,
+	limit: usize,
 }
 
-impl <'a> Ctx<'a> {
-	fn new(data: &'a [u8]) -> Self {
+impl <'a, O: Out> Ctx<'a, O> {
+	fn new(data: &'a [u8], out: O, limit: usize) -> Self {
 		Ctx {
-			out: Vec::with_capacity(0xFFF0), // TODO I am not sure allocating here is good. Probably more performant to do it outside.
+			out,
 			data,
 			pos: 0,
+			limit,
+		}
+	}
+
+	fn reserve(&self, n: usize) -> Result<()> {
+		if n > self.limit - self.out.len() {
+			return Err(ioe!(InvalidData, "chunk exceeds output limit of {} bytes", self.limit))
 		}
+		Ok(())
 	}
 
 	fn u8(&mut self) -> Result<u8> {
@@ -43,6 +107,7 @@ impl <'a> Ctx<'a> {
 	}
 
 	fn constant(&mut self, n: usize) -> Result<()> {
+		self.reserve(n)?;
 		let b = self.u8()?;
 		for _ in 0..n {
 			self.out.push(b);
@@ -51,6 +116,7 @@ impl <'a> Ctx<'a> {
 	}
 
 	fn verbatim(&mut self, n: usize) -> Result<()> {
+		self.reserve(n)?;
 		for _ in 0..n {
 			let b = self.u8()?;
 			self.out.push(b);
@@ -62,28 +128,27 @@ impl <'a> Ctx<'a> {
 		if !(1..=self.out.len()).contains(&o) {
 			return Err(ioe!(InvalidData, "tried to repeat {n} bytes from offset -{o} (length {len})", len=self.out.len()))
 		}
-		for _ in 0..n {
-			self.out.push(self.out[self.out.len()-o]);
-		}
+		self.reserve(n)?;
+		self.out.repeat(n, o);
 		Ok(())
 	}
 }
 
 #[derive(derive_more::Deref, derive_more::DerefMut)]
-struct ByteCtx<'b> {
+struct ByteCtx<'b, O> {
 	#[deref]
 	#[deref_mut]
-	ctx: Ctx<'b>,
+	ctx: Ctx<'b, O>,
 	bits: u16,
 	// Zero's decompressor counts number of remaining bits instead,
 	// but this method is simpler.
 	nextbit: u16,
 }
 
-impl <'a> ByteCtx<'a> {
-	fn new(data: &'a [u8]) -> Self {
+impl <'a, O: Out> ByteCtx<'a, O> {
+	fn new(data: &'a [u8], out: O, limit: usize) -> Self {
 		ByteCtx {
-			ctx: Ctx::new(data),
+			ctx: Ctx::new(data, out, limit),
 			bits: 0,
 			nextbit: 0,
 		}
@@ -127,8 +192,8 @@ impl <'a> ByteCtx<'a> {
 	}
 }
 
-fn decompress1(data: &[u8]) -> Result<Vec<u8>> {
-	let mut c = ByteCtx::new(data);
+fn decompress1<O: Out>(data: &[u8], out: O, limit: usize) -> Result<O> {
+	let mut c = ByteCtx::new(data, out, limit);
 	c.renew_bits()?;
 	c.nextbit <<= 8;
 
@@ -162,8 +227,8 @@ fn decompress1(data: &[u8]) -> Result<Vec<u8>> {
 }
 
 #[bitmatch::bitmatch]
-fn decompress2(data: &[u8]) -> Result<Vec<u8>> {
-	let mut c = Ctx::new(data);
+fn decompress2<O: Out>(data: &[u8], out: O, limit: usize) -> Result<O> {
+	let mut c = Ctx::new(data, out, limit);
 
 	let mut last_o = 0;
 	while c.pos < c.data.len() {
@@ -189,27 +254,269 @@ fn decompress2(data: &[u8]) -> Result<Vec<u8>> {
 	Ok(c.out)
 }
 
-pub fn decompress_chunk(data: &[u8]) -> Result<Vec<u8>> {
+/// Limits on how much data decompression may produce, for dealing with untrusted input.
+///
+/// Without limits, a single chunk of less than 64KiB can expand to tens of megabytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Maximum size of each decompressed chunk.
+	pub chunk: usize,
+	/// Maximum total decompressed size.
+	pub total: usize,
+	/// If set, the decompressed data must be exactly this long.
+	pub exact: Option<usize>,
+}
+
+impl Limits {
+	pub const NONE: Limits = Limits {
+		chunk: usize::MAX,
+		total: usize::MAX,
+		exact: None,
+	};
+
+	/// The default limits, additionally requiring the output to be exactly `len` bytes.
+	pub fn exact(len: usize) -> Self {
+		Limits {
+			exact: Some(len),
+			..Self::default()
+		}
+	}
+
+	/// The most the next chunk may produce, given that `total` bytes have been produced so far.
+	fn remaining(&self, total: usize) -> usize {
+		let max = self.total.min(self.exact.unwrap_or(usize::MAX));
+		self.chunk.min(max.saturating_sub(total))
+	}
+
+	fn finish(&self, total: usize) -> Result<()> {
+		if let Some(exact) = self.exact && total != exact {
+			return Err(ioe!(InvalidData, "decompressed to {total} bytes, expected {exact}"))
+		}
+		Ok(())
+	}
+}
+
+impl Default for Limits {
+	/// Chunks are limited to the 0xFFF0 bytes that the games use, and the total to 64MiB.
+	fn default() -> Self {
+		Limits {
+			chunk: 0xFFF0,
+			total: 64 << 20,
+			exact: None,
+		}
+	}
+}
+
+fn decompress_chunk_to<O: Out>(data: &[u8], out: O, limit: usize) -> Result<O> {
 	if data.first() == Some(&0) {
-		Ok(decompress1(data)?)
+		decompress1(data, out, limit)
 	} else {
-		Ok(decompress2(data)?)
+		decompress2(data, out, limit)
 	}
 }
 
+pub fn decompress_chunk(data: &[u8]) -> Result<Vec<u8>> {
+	let mut out = Vec::new();
+	decompress_chunk_into(data, &mut out)?;
+	Ok(out)
+}
+
+/// Decompresses a chunk, appending it to `out`.
+pub fn decompress_chunk_into(data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+	decompress_chunk_into_with(data, out, &Limits::NONE)
+}
+
+/// Decompresses a chunk, failing if the output does not fit within the limits.
+pub fn decompress_chunk_with(data: &[u8], limits: &Limits) -> Result<Vec<u8>> {
+	let mut out = Vec::new();
+	decompress_chunk_into_with(data, &mut out, limits)?;
+	Ok(out)
+}
+
+/// Like [`decompress_chunk_with`], but appends to `out`.
+pub fn decompress_chunk_into_with(data: &[u8], out: &mut Vec<u8>, limits: &Limits) -> Result<()> {
+	let start = out.len();
+	decompress_chunk_to(data, VecOut::new(out), limits.remaining(0))?;
+	limits.finish(out.len() - start)
+}
+
 pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
 	let mut out = Vec::new();
-	for chunk in decompress_stream(&mut std::io::Cursor::new(data)) {
-		out.append(&mut chunk?);
-	}
+	decompress_into(data, &mut out)?;
+	Ok(out)
+}
+
+/// Decompresses a chunk stream, appending it to `out`.
+pub fn decompress_into(data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+	decompress_into_with(data, out, &Limits::NONE)
+}
+
+/// Decompresses a chunk stream, failing if the output does not fit within the limits.
+pub fn decompress_with(data: &[u8], limits: &Limits) -> Result<Vec<u8>> {
+	let mut out = Vec::new();
+	decompress_into_with(data, &mut out, limits)?;
 	Ok(out)
 }
 
+/// Like [`decompress_with`], but appends to `out`.
+pub fn decompress_into_with(data: &[u8], out: &mut Vec<u8>, limits: &Limits) -> Result<()> {
+	let start = out.len();
+	for chunk in chunk_iter(data) {
+		let limit = limits.remaining(out.len() - start);
+		decompress_chunk_to(chunk?, VecOut::new(out), limit)?;
+	}
+	limits.finish(out.len() - start)
+}
+
+/// Calculates the exact size of the decompressed data, without decompressing it.
+pub fn decompressed_size(data: &[u8]) -> Result<usize> {
+	let mut size = 0;
+	for chunk in chunk_iter(data) {
+		size += decompress_chunk_to(chunk?, CountOut(0), usize::MAX)?.0;
+	}
+	Ok(size)
+}
+
+fn chunk_iter(data: &[u8]) -> impl Iterator<Item=Result<&[u8]>> {
+	fn next<'a>(data: &'a [u8], pos: &mut usize) -> Result<(&'a [u8], bool)> {
+		let chunklen = data.get(*pos..*pos+2).ok_or_else(|| ioe!(UnexpectedEof, "missing chunk len"))?;
+		let chunklen = u16::from_le_bytes([chunklen[0], chunklen[1]]) as usize;
+		let chunklen = chunklen.checked_sub(2).ok_or_else(|| ioe!(InvalidData, "chunk len < 2"))?;
+		*pos += 2;
+		let chunk = data.get(*pos..*pos+chunklen).ok_or_else(|| ioe!(UnexpectedEof, "chunk extends past end"))?;
+		*pos += chunklen;
+		let has_next = *data.get(*pos).ok_or_else(|| ioe!(UnexpectedEof, "missing has_next"))? != 0;
+		*pos += 1;
+		Ok((chunk, has_next))
+	}
+
+	let mut has_next = true;
+	let mut pos = 0;
+	std::iter::from_fn(move || {
+		if !has_next {
+			return None
+		}
+		let r = next(data, &mut pos);
+		has_next = matches!(r, Ok((_, true)));
+		Some(r.map(|a| a.0))
+	})
+}
+
+/// Splits a chunk stream into its compressed chunks, without decompressing them.
+pub fn chunks(data: &[u8]) -> Result<Vec<&[u8]>> {
+	chunk_iter(data).collect()
+}
+
+/// Whether `data` is a chunk stream, that is, whether its chunk framing is well-formed and ends
+/// exactly at the end of the data. The chunks themselves are not checked.
+///
+/// There is nothing else marking a file as compressed, so this is how [`Archives`] and [`Dir`]
+/// tell compressed files from uncompressed ones.
+///
+/// [`Archives`]: crate::archive::Archives
+/// [`Dir`]: crate::vfs::Dir
+pub fn is_compressed(data: &[u8]) -> bool {
+	chunk_iter(data)
+		.try_fold(0, |len, chunk| chunk.map(|chunk| len + 2 + chunk.len() + 1))
+		.is_ok_and(|len| len == data.len())
+}
+
+/// Decompresses a chunk stream from `data` into `out`, returning the number of bytes written.
+///
+/// The buffers are reused between chunks, so this does not allocate for each chunk.
+pub fn decompress_to(data: impl Read, out: &mut impl Write) -> Result<u64> {
+	let mut reader = Reader::new(data);
+	let mut total = 0;
+	while reader.next_chunk()? {
+		out.write_all(&reader.buf)?;
+		total += reader.buf.len() as u64;
+	}
+	Ok(total)
+}
+
+/// A reader that decompresses a chunk stream lazily, one chunk at a time.
+pub struct Reader<R> {
+	inner: R,
+	limits: Limits,
+	has_next: bool,
+	inbuf: Vec<u8>,
+	buf: Vec<u8>,
+	pos: usize,
+	total: usize,
+}
+
+impl<R: Read> Reader<R> {
+	pub fn new(inner: R) -> Self {
+		Self::with_limits(inner, Limits::NONE)
+	}
+
+	pub fn with_limits(inner: R, limits: Limits) -> Self {
+		Reader {
+			inner,
+			limits,
+			has_next: true,
+			inbuf: Vec::new(),
+			buf: Vec::new(),
+			pos: 0,
+			total: 0,
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+
+	/// Reads and decompresses the next chunk into `buf`, returning false at the end of the stream.
+	fn next_chunk(&mut self) -> Result<bool> {
+		if !self.has_next {
+			return Ok(false)
+		}
+		self.has_next = false;
+		let mut buf2 = [0u8;2];
+		self.inner.read_exact(&mut buf2)?;
+		let chunklen = u16::from_le_bytes(buf2) as usize;
+		let chunklen = chunklen.checked_sub(2).ok_or_else(|| ioe!(InvalidData, "chunk len < 2"))?;
+		self.inbuf.resize(chunklen, 0);
+		self.inner.read_exact(&mut self.inbuf)?;
+		self.buf.clear();
+		self.pos = 0;
+		let limit = self.limits.remaining(self.total);
+		decompress_chunk_to(&self.inbuf, VecOut::new(&mut self.buf), limit)?;
+		self.total += self.buf.len();
+		self.has_next = u8(&mut self.inner)? != 0;
+		if !self.has_next {
+			self.limits.finish(self.total)?;
+		}
+		Ok(true)
+	}
+}
+
+impl<R: Read> Read for Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		while self.pos == self.buf.len() {
+			if !self.next_chunk()? {
+				return Ok(0)
+			}
+		}
+		let n = (self.buf.len() - self.pos).min(buf.len());
+		buf[..n].copy_from_slice(&self.buf[self.pos..self.pos+n]);
+		self.pos += n;
+		Ok(n)
+	}
+}
+
 pub fn decompress_stream(data: &mut impl Read) -> impl Iterator<Item=Result<Vec<u8>>> + '_ {
+	decompress_stream_with(data, Limits::NONE)
+}
+
+/// Like [`decompress_stream`], but fails once the output exceeds the limits.
+pub fn decompress_stream_with(data: &mut impl Read, limits: Limits) -> impl Iterator<Item=Result<Vec<u8>>> + '_ {
 	let mut has_next = true;
+	let mut total = 0;
 	let mut buf = Vec::new();
 	let mut buf2 = [0u8;2];
 	std::iter::from_fn(move || has_next.then(|| {
+		has_next = false;
 		data.read_exact(&mut buf2)?;
 		let chunklen = u16::from_le_bytes(buf2) as usize;
 		let chunklen = chunklen.checked_sub(2).ok_or_else(|| ioe!(InvalidData, "chunk len < 2"))?;
@@ -218,8 +525,50 @@ pub fn decompress_stream(data: &mut impl Read) -> impl Iterator<Item=Result<Vec<
 		}
 		let buf = &mut buf[..chunklen];
 		data.read_exact(buf)?;
-		let chunk = decompress_chunk(buf)?;
+		let mut chunk = Vec::new();
+		decompress_chunk_to(buf, VecOut::new(&mut chunk), limits.remaining(total))?;
+		total += chunk.len();
 		has_next = u8(data)? != 0;
+		if !has_next {
+			limits.finish(total)?;
+		}
 		Ok(chunk)
 	}))
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::compress::{self, Mode, CHUNK_SIZE};
+
+	#[test]
+	fn chunk_limits() {
+		let chunk = compress::compress_chunk(&[0; 1000], Mode::Bytes, 6);
+		let limits = |chunk| Limits { chunk, ..Limits::NONE };
+		assert_eq!(decompress_chunk_with(&chunk, &limits(1000)).unwrap(), [0; 1000]);
+		assert!(decompress_chunk_with(&chunk, &limits(999)).is_err());
+		assert!(decompress_chunk_with(&chunk, &Limits { exact: Some(1001), ..Limits::NONE }).is_err());
+
+		let mut out = vec![1];
+		assert!(decompress_chunk_into_with(&chunk, &mut out, &Limits::exact(1000)).is_ok());
+		assert_eq!(out.len(), 1001);
+	}
+
+	#[test]
+	fn stream_limits() {
+		let data = compress::compress(&[0; CHUNK_SIZE * 2], Mode::Bits, 6);
+		let limits = |total| Limits { total, ..Limits::default() };
+		let chunks = decompress_stream_with(&mut &data[..], limits(CHUNK_SIZE * 2)).collect::<Result<Vec<_>>>().unwrap();
+		assert_eq!(chunks.len(), 2);
+
+		let mut reader = &data[..];
+		let mut chunks = decompress_stream_with(&mut reader, limits(CHUNK_SIZE + 1));
+		assert_eq!(chunks.next().unwrap().unwrap().len(), CHUNK_SIZE);
+		assert!(chunks.next().unwrap().is_err());
+
+		let mut reader = &data[..];
+		let mut chunks = decompress_stream_with(&mut reader, Limits::exact(CHUNK_SIZE * 2 + 1));
+		assert!(chunks.next().unwrap().is_ok());
+		assert!(chunks.next().unwrap().is_err());
+	}
+}
@@ -1,43 +1,153 @@
+use std::path::Path;
+use std::sync::Arc;
+
 use crate::scena::code::InstructionSet;
+use crate::util::Encoding;
+
+pub mod install;
 
 type Backtrace = Box<std::backtrace::Backtrace>;
 
+/// Everything needed to read and write a game's files.
+///
+/// A `GameData<'static>` with an owned lookup can be cloned cheaply and shared between threads.
+#[derive(Clone)]
 pub struct GameData<'a> {
 	pub iset: InstructionSet,
-	pub lookup: &'a dyn Lookup,
+	pub lookup: LookupRef<'a>,
 	pub kai: bool,
+	pub encoding: Encoding,
+	/// Whether the files are from Geofront's fan translation, whose text for Azure uses the extra
+	/// control codes 0x0A and 0x0C.
+	pub geofront: bool,
+}
+
+// GameData is often shared between threads, such as by parallel batch jobs.
+const _: () = {
+	const fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<GameData<'static>>();
+};
+
+impl GameData<'_> {
+	/// Creates a `GameData` that owns its lookup.
+	pub fn new(iset: InstructionSet, lookup: Arc<dyn Lookup>, kai: bool) -> GameData<'static> {
+		GameData {
+			iset,
+			lookup: LookupRef::Owned(lookup),
+			kai,
+			encoding: Encoding::Cp932,
+			geofront: false,
+		}
+	}
+
+	pub fn with_encoding(self, encoding: Encoding) -> Self {
+		GameData { encoding, ..self }
+	}
+
+	/// Detects which game is installed at `path`, and returns a `GameData` for it.
+	///
+	/// See [`Install::detect`](install::Install::detect) for what is supported.
+	pub fn from_install(path: impl AsRef<Path>) -> Result<GameData<'static>, install::Error> {
+		install::Install::detect(path)?.game_data()
+	}
+}
+
+/// Either a borrowed or a shared [`Lookup`].
+#[derive(Clone)]
+pub enum LookupRef<'a> {
+	Borrowed(&'a dyn Lookup),
+	Owned(Arc<dyn Lookup>),
+}
+
+impl<'a> std::ops::Deref for LookupRef<'a> {
+	type Target = dyn Lookup + 'a;
+
+	fn deref(&self) -> &Self::Target {
+		match self {
+			LookupRef::Borrowed(a) => *a,
+			LookupRef::Owned(a) => a.as_ref(),
+		}
+	}
+}
+
+impl<'a> From<&'a dyn Lookup> for LookupRef<'a> {
+	fn from(lookup: &'a dyn Lookup) -> Self {
+		LookupRef::Borrowed(lookup)
+	}
+}
+
+impl From<Arc<dyn Lookup>> for LookupRef<'_> {
+	fn from(lookup: Arc<dyn Lookup>) -> Self {
+		LookupRef::Owned(lookup)
+	}
 }
 
 impl GameData<'static> {
 	pub const ZERO: &GameData<'static> = &GameData {
 		iset: InstructionSet::Zero,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: false,
 	};
 	pub const ZERO_EVO: &GameData<'static> = &GameData {
 		iset: InstructionSet::ZeroEvo,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: false,
 	};
 	pub const ZERO_KAI: &GameData<'static> = &GameData {
 		iset: InstructionSet::Zero,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: true,
+		encoding: Encoding::Cp932,
+		geofront: false,
 	};
 	pub const AO: &GameData<'static> = &GameData {
 		iset: InstructionSet::Ao,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: false,
 	};
 	pub const AO_EVO: &GameData<'static> = &GameData {
 		iset: InstructionSet::AoEvo,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: false,
 	};
 	pub const AO_KAI: &GameData<'static> = &GameData {
 		iset: InstructionSet::Ao,
-		lookup: &ED7Lookup,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
 		kai: true,
+		encoding: Encoding::Cp932,
+		geofront: false,
+	};
+	/// Geofront's English translation of Zero.
+	pub const ZERO_GF: &GameData<'static> = &GameData {
+		iset: InstructionSet::Zero,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
+		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: true,
+	};
+	/// Geofront's English translation of Azure.
+	pub const AO_GF: &GameData<'static> = &GameData {
+		iset: InstructionSet::Ao,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
+		kai: false,
+		encoding: Encoding::Cp932,
+		geofront: true,
+	};
+	/// The Chinese PC release, which is what Geofront's translation is based on.
+	pub const AO_CN: &GameData<'static> = &GameData {
+		iset: InstructionSet::Ao,
+		lookup: LookupRef::Borrowed(&ED7Lookup),
+		kai: false,
+		encoding: Encoding::Gbk,
+		geofront: false,
 	};
 }
 
@@ -48,6 +158,9 @@ pub enum LookupError {
 
 	#[error("failed to look up 0x{index:08X}")]
 	Index { index: u32, backtrace: Backtrace },
+
+	#[error(transparent)]
+	Archive { #[from] #[backtrace] source: crate::archive::Error },
 }
 
 impl std::fmt::Debug for LookupError {
@@ -55,6 +168,7 @@ impl std::fmt::Debug for LookupError {
 		match self {
 			Self::Name { name, backtrace } => f.debug_struct("Name").field("name", name).field("backtrace", backtrace).finish(),
 			Self::Index { index, backtrace } => f.debug_struct("Index").field("index", &format_args!("0x{:08X}", index)).field("backtrace", backtrace).finish(),
+			Self::Archive { source } => f.debug_struct("Archive").field("source", source).finish(),
 		}
 	}
 }
@@ -77,7 +191,7 @@ impl std::convert::From<u32> for LookupError {
 	}
 }
 
-pub trait Lookup {
+pub trait Lookup: Send + Sync {
 	fn name(&self, index: u32) -> Result<String, LookupError>;
 	fn index(&self, name: &str) -> Result<u32, LookupError>;
 }
@@ -124,115 +238,185 @@ impl<T: Lookup> Lookup for SkyGameData<T> {
 
 impl Lookup for crate::archive::Archives {
 	fn name(&self, a: u32) -> Result<String, LookupError> {
-		self.name(a).map(str::to_owned).ok_or_else(|| a.into())
+		self.name(a)?.map(str::to_owned).ok_or_else(|| a.into())
 	}
 
 	fn index(&self, name: &str) -> Result<u32, LookupError> {
-		self.index(name).ok_or_else(|| name.into())
+		self.index(name)?.ok_or_else(|| name.into())
 	}
 }
 
+/// How the low 20 bits of an ED7 file reference are written in the file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ed7Format {
+	/// The given number of hex digits.
+	Hex(usize),
+	/// The given number of decimal digits.
+	Dec(usize),
+	/// Four hex digits, followed by `_` and a fifth digit if that is nonzero.
+	Scena,
+	/// An index into a list of names.
+	Names(&'static [&'static str]),
+}
+
+/// The known ranges of ED7 file references: the high 12 bits of the index, and the prefix, format,
+/// and suffix of the file name.
+///
+/// The chr, apl, monster, scena and battle ranges have been seen in references in the games' files.
+/// The others are numbered after the ED6 archive holding the same kind of file; the lookup roundtrip
+/// checks every range against the games' file listings.
+const ED7_FILES: &[(u32, &str, Ed7Format, &str)] = &[
+	(0x001, "map/m",         Ed7Format::Hex(4),  ".it3"),
+	(0x002, "text/",         Ed7Format::Names(ED7_TEXT), "._dt"),
+	(0x003, "effect/eff/ef", Ed7Format::Hex(4),  ".eff"),
+	(0x004, "visual/c_vis",  Ed7Format::Hex(3),  ".itp"),
+	(0x005, "ops/e",         Ed7Format::Hex(4),  ".op2"),
+	(0x007, "chr/ch",        Ed7Format::Hex(5),  ".itc"),
+	(0x008, "apl/ch",        Ed7Format::Hex(5),  ".itc"),
+	(0x009, "monster/ch",    Ed7Format::Hex(5),  ".itc"),
+	(0x00A, "bgm/ed7",       Ed7Format::Dec(3),  ".ogg"),
+	(0x00B, "se/ed7s",       Ed7Format::Dec(4),  ".wav"),
+	(0x210, "scena/0",       Ed7Format::Scena,   ".bin"),
+	(0x211, "scena/a",       Ed7Format::Scena,   ".bin"),
+	(0x212, "scena/t",       Ed7Format::Scena,   ".bin"),
+	(0x213, "scena/c",       Ed7Format::Scena,   ".bin"),
+	(0x214, "scena/r",       Ed7Format::Scena,   ".bin"),
+	(0x215, "scena/m",       Ed7Format::Scena,   ".bin"),
+	(0x216, "scena/e",       Ed7Format::Scena,   ".bin"),
+	(0x217, "scena/b",       Ed7Format::Scena,   ".bin"),
+	(0x300, "battle/dat/ms", Ed7Format::Hex(5),  ".dat"),
+	(0x301, "battle/dat/as", Ed7Format::Hex(5),  ".dat"),
+	(0x302, "battle/dat/bs", Ed7Format::Hex(5),  ".dat"),
+];
+
+/// The tables in `text/`, in alphabetical order.
+const ED7_TEXT: &[&str] = &[
+	"t_bgmtbl", "t_btlset", "t_cook2", "t_exp", "t_face", "t_item", "t_item2", "t_magic",
+	"t_name", "t_orb", "t_quest", "t_se", "t_shop", "t_status", "t_town", "t_world",
+];
+
 #[derive(Debug, Clone, Copy)]
 pub struct ED7Lookup;
 
 impl Lookup for ED7Lookup {
 	fn name(&self, index: u32) -> Result<String, LookupError> {
-		match (index & 0xFF000000) >> 24 {
-			0x00 => {
-				let a = (index & 0xF00000) >> 20;
-				let b = index & 0x0FFFFF;
-				let prefix = match a {
-					7 => "chr",
-					8 => "apl",
-					9 => "monster",
-					_ => return Err(index.into())
-				};
-				Ok(format!("{prefix}/ch{b:05x}.itc"))
-			}
-
-			0x21 => {
-				let a = (index & 0xF00000) >> 20;
-				let b = (index & 0x0FFFF0) >> 4;
-				let c = index & 0x00000F;
-				let prefix = "0atcrmeb".chars().nth(a as usize).ok_or(index)?;
-				if c == 0 {
-					Ok(format!("scena/{prefix}{b:04x}.bin"))
-				} else {
-					Ok(format!("scena/{prefix}{b:04x}_{c:01x}.bin"))
-				}
-			}
-
-			0x30 => {
-				let a = (index & 0xF00000) >> 20;
-				let b = index & 0x0FFFFF;
-				let prefix = match a {
-					0 => "ms",
-					1 => "as",
-					2 => "bs",
-					_ => return Err(index.into())
-				};
-				Ok(format!("battle/dat/{prefix}{b:05x}.dat"))
-			}
-
-			_ => Err(index.into())
-		}
+		let &(_, prefix, format, suffix) = ED7_FILES.iter()
+			.find(|a| a.0 == index >> 20)
+			.ok_or(index)?;
+		let b = index & 0xFFFFF;
+		Ok(match format {
+			Ed7Format::Hex(n) if b < 1 << (4 * n) => format!("{prefix}{b:0n$x}{suffix}"),
+			Ed7Format::Dec(n) if b < 10u32.pow(n as u32) => format!("{prefix}{b:0n$}{suffix}"),
+			Ed7Format::Scena if b & 0xF == 0 => format!("{prefix}{:04x}{suffix}", b >> 4),
+			Ed7Format::Scena => format!("{prefix}{:04x}_{:01x}{suffix}", b >> 4, b & 0xF),
+			Ed7Format::Names(names) if (b as usize) < names.len() => format!("{prefix}{}{suffix}", names[b as usize]),
+			_ => return Err(index.into()),
+		})
 	}
 
+	/// Numbers can have any number of digits, hex digits in either case, and scena names can have
+	/// a `_0` suffix, so several names can give the same index; [`name`](Self::name) gives the
+	/// canonical one. Numbers too large for their part of the index are rejected.
 	fn index(&self, name: &str) -> Result<u32, LookupError> {
+		fn num(s: &str, radix: u32, max: u32) -> Option<u32> {
+			u32::from_str_radix(s, radix).ok().filter(|a| *a < max)
+		}
+
 		fn inner(name: &str) -> Option<u32> {
-			if let Some(name) = name.strip_prefix("chr/ch") {
-				let name = name.strip_suffix(".itc")?;
-				let b = u32::from_str_radix(name, 16).ok()?;
-				return Some(0x00700000 | b)
-			}
-			if let Some(name) = name.strip_prefix("apl/ch") {
-				let name = name.strip_suffix(".itc")?;
-				let b = u32::from_str_radix(name, 16).ok()?;
-				return Some(0x00800000 | b)
-			}
-			if let Some(name) = name.strip_prefix("monster/ch") {
-				let name = name.strip_suffix(".itc")?;
-				let b = u32::from_str_radix(name, 16).ok()?;
-				return Some(0x00900000 | b)
-			}
-
-			if let Some(name) = name.strip_prefix("scena/") {
-				let name = name.strip_suffix(".bin")?;
-				let mut iter = name.chars();
-				let a = iter.next()?;
-				let name = iter.as_str();
-				let a = "0atcrmeb".chars().position(|x| x == a)? as u32;
-				let (b, c) = if let Some((b, c)) = name.split_once('_') {
-					let b = u32::from_str_radix(b, 16).ok()?;
-					let c = u32::from_str_radix(c, 16).ok()?;
-					(b, c)
-				} else {
-					let b = u32::from_str_radix(name, 16).ok()?;
-					(b, 0)
+			ED7_FILES.iter().find_map(|&(id, prefix, format, suffix)| {
+				let b = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+				let b = match format {
+					Ed7Format::Hex(n) => num(b, 16, 1 << (4 * n))?,
+					Ed7Format::Dec(n) => num(b, 10, 10u32.pow(n as u32))?,
+					Ed7Format::Scena => match b.split_once('_') {
+						Some((b, c)) => num(b, 16, 1 << 16)? << 4 | num(c, 16, 1 << 4)?,
+						None => num(b, 16, 1 << 16)? << 4,
+					},
+					Ed7Format::Names(names) => names.iter().position(|a| *a == b)? as u32,
 				};
-				return Some(0x21000000 | a << 20 | b << 4 | c)
-			}
-
-			if let Some(name) = name.strip_prefix("battle/dat/") {
-				if let Some(name) = name.strip_prefix("ms") {
-					let name = name.strip_suffix(".dat")?;
-					let b = u32::from_str_radix(name, 16).ok()?;
-					return Some(0x30000000 | b)
-				}
-				if let Some(name) = name.strip_prefix("as") {
-					let name = name.strip_suffix(".dat")?;
-					let b = u32::from_str_radix(name, 16).ok()?;
-					return Some(0x30100000 | b)
-				}
-				if let Some(name) = name.strip_prefix("bs") {
-					let name = name.strip_suffix(".dat")?;
-					let b = u32::from_str_radix(name, 16).ok()?;
-					return Some(0x30200000 | b)
-				}
-			}
-
-			None
+				Some(id << 20 | b)
+			})
 		}
 		inner(name).ok_or_else(|| name.into())
 	}
 }
+
+/// Writes file references as their index in hex, for when the names are not known.
+///
+/// Any reference is accepted, so this is enough to read and write files that refer to ones that are
+/// not available, such as the Evolution releases of the Sky games, which refer to files by their
+/// index in the PC archives.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexLookup;
+
+impl Lookup for IndexLookup {
+	fn name(&self, index: u32) -> Result<String, LookupError> {
+		Ok(format!("{index:08x}"))
+	}
+
+	fn index(&self, name: &str) -> Result<u32, LookupError> {
+		u32::from_str_radix(name, 16).map_err(|_| name.into())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn ed7_lookup() {
+		for (index, name) in [
+			(0x0010_0120, "map/m0120.it3"),
+			(0x0020_0000, "text/t_bgmtbl._dt"),
+			(0x0020_000F, "text/t_world._dt"),
+			(0x0030_0a01, "effect/eff/ef0a01.eff"),
+			(0x0040_0012, "visual/c_vis012.itp"),
+			(0x0050_3000, "ops/e3000.op2"),
+			(0x0070_012c, "chr/ch0012c.itc"),
+			(0x0080_0001, "apl/ch00001.itc"),
+			(0x0090_2000, "monster/ch02000.itc"),
+			(0x00A0_0064, "bgm/ed7100.ogg"),
+			(0x00B0_0100, "se/ed7s0256.wav"),
+			(0x2100_0000, "scena/00000.bin"),
+			(0x2120_1001, "scena/t0100_1.bin"),
+			(0x2170_0100, "scena/b0010.bin"),
+			(0x3020_0031, "battle/dat/bs00031.dat"),
+		] {
+			assert_eq!(ED7Lookup.index(name).ok(), Some(index), "{name}");
+			assert_eq!(ED7Lookup.name(index).ok().as_deref(), Some(name), "0x{index:08X}");
+		}
+	}
+
+	#[test]
+	fn ed7_lookup_aliases() {
+		for (index, name) in [
+			(0x2120_1000, "scena/t0100_0.bin"),
+			(0x2120_1000, "scena/t100.bin"),
+			(0x0070_012c, "chr/ch12c.itc"),
+			(0x0070_012c, "chr/ch0012C.itc"),
+			(0x00A0_0064, "bgm/ed70100.ogg"),
+		] {
+			assert_eq!(ED7Lookup.index(name).ok(), Some(index), "{name}");
+		}
+	}
+
+	#[test]
+	fn ed7_lookup_invalid() {
+		for name in [
+			"chr/ch100000.itc",
+			"scena/t10000.bin",
+			"scena/t0100_10.bin",
+			"chr/ch0012c.itp",
+			"unknown/ch0012c.itc",
+			"map/m10000.it3",
+			"bgm/ed70a0.ogg",
+			"bgm/ed71000.ogg",
+			"text/t_unknown._dt",
+		] {
+			assert!(ED7Lookup.index(name).is_err(), "{name}");
+		}
+		for index in [0x0000_0000, 0x0011_0000, 0x0020_0010, 0x0040_1000, 0x00A0_03E8, 0x2180_0000, 0x3030_0000] {
+			assert!(ED7Lookup.name(index).is_err(), "0x{index:08X}");
+		}
+	}
+}
@@ -0,0 +1,469 @@
+//! Detecting which game is installed in a directory.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::archive::Archives;
+use crate::decompress;
+use crate::scena::code::InstructionSet;
+use crate::util::Encoding;
+use super::{GameData, Lookup, LookupRef, ED7Lookup, IndexLookup};
+
+type Backtrace = Box<std::backtrace::Backtrace>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("{source}")]
+	Io { #[from] source: std::io::Error, backtrace: Backtrace },
+
+	#[error(transparent)]
+	Archive { #[from] #[backtrace] source: crate::archive::Error },
+
+	#[error("could not identify the game in {}", path.display())]
+	Unknown { path: PathBuf, backtrace: Backtrace },
+
+	#[error("the text encoding of {game:?} {platform:?} cannot be detected, and must be given separately")]
+	NeedsEncoding { game: Game, platform: Platform, backtrace: Backtrace },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Game {
+	Fc,
+	Sc,
+	Tc,
+	Zero,
+	Ao,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+	/// The original PC releases, by Falcom or XSEED for the Sky games.
+	Pc,
+	/// The PSP releases.
+	Psp,
+	/// The Evolution releases on Vita.
+	Vita,
+	/// The PC releases with Geofront's fan translation installed.
+	Geofront,
+	/// NISA's PC releases, based on the Kai versions.
+	Nisa,
+}
+
+/// A detected game installation.
+#[derive(Debug, Clone)]
+pub struct Install {
+	pub path: PathBuf,
+	pub game: Game,
+	pub platform: Platform,
+	/// Directories holding the game's files, from lowest to highest priority, like the layers of a
+	/// [`Vfs`](crate::vfs::Vfs). For the PC releases of the Sky games, this is the directory
+	/// containing the archives.
+	pub dirs: Vec<PathBuf>,
+	/// Suffix of the translated `text` and `scena` directories, such as `_us` for `text_us`.
+	pub lang_suffix: Option<&'static str>,
+	/// The encoding of the game's own text, if it is known.
+	///
+	/// The PC releases of Zero and Ao were published in both Japanese and Chinese, with nothing in
+	/// the directory layout to tell them apart, so for those it is guessed from the names in
+	/// `text/t_name._dt`. If that file is missing or does not settle it, this is `None` and needs to
+	/// be set before creating a [`GameData`].
+	///
+	/// For Geofront's translation this is the encoding of the base game in `data`, which is GBK for
+	/// Ao since it is built on the Chinese release. The translated files in `data_en` are SJIS.
+	pub encoding: Option<Encoding>,
+}
+
+impl Install {
+	/// Detects which game is installed at `path`.
+	///
+	/// `path` should be the game's root directory: the one with the executable on PC, the one
+	/// containing `PSP_GAME` for PSP, and the one containing `sce_sys` for Vita.
+	pub fn detect(path: impl AsRef<Path>) -> Result<Install, Error> {
+		let path = path.as_ref();
+		let install = |game, platform, dirs, lang_suffix| Ok(Install {
+			path: path.to_owned(),
+			game,
+			platform,
+			dirs,
+			lang_suffix,
+			encoding: match (game, platform) {
+				(Game::Zero | Game::Ao, Platform::Pc | Platform::Geofront) => guess_encoding(&path.join("data")),
+				_ => Some(Encoding::Cp932),
+			},
+		});
+
+		let psp = path.join("PSP_GAME");
+		if psp.is_dir() {
+			let game = sfo_title(&psp.join("PARAM.SFO")).and_then(|a| game_from_title(&a));
+			if let Some(game @ (Game::Zero | Game::Ao)) = game {
+				return install(game, Platform::Psp, vec![psp.join("USRDIR/data")], None)
+			}
+			return Err(unknown(path))
+		}
+
+		let title = sfo_title(&path.join("sce_sys/param.sfo"));
+		let game = title.as_deref().and_then(game_from_title);
+		let evo = path.join("gamedata/data");
+		if evo.is_dir() {
+			for (dir, game) in [("data_3rd", Game::Tc), ("data_sc", Game::Sc), ("data", Game::Fc)] {
+				if evo.join(dir).is_dir() {
+					return install(game, Platform::Vita, vec![evo.join(dir)], None)
+				}
+			}
+		}
+		if path.join("data/data").is_dir() && path.join("data1/data").is_dir() {
+			return match game {
+				Some(game @ (Game::Zero | Game::Ao)) => {
+					install(game, Platform::Vita, vec![path.join("data/data"), path.join("data1/data")], None)
+				}
+				_ => Err(unknown(path)),
+			}
+		}
+
+		let mut exes = Vec::new();
+		for ent in std::fs::read_dir(path)? {
+			if let Some(name) = ent?.file_name().to_str() {
+				exes.push(name.to_lowercase());
+			}
+		}
+		let has_exe = |prefix: &str| exes.iter().any(|a| a.starts_with(prefix) && a.ends_with(".exe"));
+
+		let ed7 = if has_exe("ed_zero") {
+			Some(Game::Zero)
+		} else if has_exe("ed_ao") {
+			Some(Game::Ao)
+		} else {
+			None
+		};
+		if let Some(game) = ed7 {
+			let data = path.join("data");
+			return if data.join("text_us").is_dir() {
+				install(game, Platform::Nisa, vec![data], Some("_us"))
+			} else if path.join("data_en").is_dir() {
+				install(game, Platform::Geofront, vec![data, path.join("data_en")], None)
+			} else {
+				install(game, Platform::Pc, vec![data], None)
+			}
+		}
+
+		let ed6 = if has_exe("ed6_win3") {
+			Some(Game::Tc)
+		} else if has_exe("ed6_win2") {
+			Some(Game::Sc)
+		} else if has_exe("ed6_win") {
+			Some(Game::Fc)
+		} else {
+			None
+		};
+		if let Some(game) = ed6 && path.join("ED6_DT00.dir").is_file() {
+			return install(game, Platform::Pc, vec![path.to_owned()], None)
+		}
+
+		Err(unknown(path))
+	}
+
+	pub fn iset(&self) -> InstructionSet {
+		let evo = self.platform == Platform::Vita;
+		match (self.game, evo) {
+			(Game::Fc, false) => InstructionSet::Fc,
+			(Game::Fc, true) => InstructionSet::FcEvo,
+			(Game::Sc, false) => InstructionSet::Sc,
+			(Game::Sc, true) => InstructionSet::ScEvo,
+			(Game::Tc, false) => InstructionSet::Tc,
+			(Game::Tc, true) => InstructionSet::TcEvo,
+			(Game::Zero, false) => InstructionSet::Zero,
+			(Game::Zero, true) => InstructionSet::ZeroEvo,
+			(Game::Ao, false) => InstructionSet::Ao,
+			(Game::Ao, true) => InstructionSet::AoEvo,
+		}
+	}
+
+	pub fn kai(&self) -> bool {
+		self.platform == Platform::Nisa
+	}
+
+	fn needs_encoding(&self) -> Result<Encoding, Error> {
+		self.encoding.clone().ok_or_else(|| Error::NeedsEncoding {
+			game: self.game,
+			platform: self.platform,
+			backtrace: std::backtrace::Backtrace::capture().into(),
+		})
+	}
+
+	/// Creates a [`GameData`] for this installation.
+	///
+	/// For the PC releases of the Sky games, this opens the archives. The Evolution releases of
+	/// the Sky games refer to files by their index in the PC archives, which they do not include,
+	/// so they get an [`IndexLookup`]; use [`game_data_with`](Self::game_data_with) with the PC
+	/// archives to get the files' names instead. Fails if the [`encoding`](Self::encoding) is not
+	/// known.
+	pub fn game_data(&self) -> Result<GameData<'static>, Error> {
+		let encoding = self.needs_encoding()?;
+		let lookup = match (self.game, self.platform) {
+			(Game::Zero | Game::Ao, _) => LookupRef::Borrowed(&ED7Lookup),
+			(_, Platform::Pc) => LookupRef::Owned(Arc::new(Archives::new(&self.path)?)),
+			_ => LookupRef::Borrowed(&IndexLookup),
+		};
+		Ok(GameData {
+			iset: self.iset(),
+			lookup,
+			kai: self.kai(),
+			encoding,
+			geofront: self.platform == Platform::Geofront,
+		})
+	}
+
+	/// Creates a [`GameData`] for this installation, using the given lookup.
+	pub fn game_data_with<'a>(&self, lookup: &'a dyn Lookup) -> Result<GameData<'a>, Error> {
+		Ok(GameData {
+			iset: self.iset(),
+			lookup: LookupRef::Borrowed(lookup),
+			kai: self.kai(),
+			encoding: self.needs_encoding()?,
+			geofront: self.platform == Platform::Geofront,
+		})
+	}
+}
+
+/// Guesses whether the game in `dir` is the Japanese or the Chinese release, from the names in
+/// `text/t_name._dt`.
+///
+/// Chinese text read as SJIS either fails or turns into halfwidth katakana, which the games do not
+/// use in names, while Japanese text is usually also valid GBK. So the names are tried as SJIS
+/// first. Returns `None` if the file cannot be read or neither encoding fits.
+fn guess_encoding(dir: &Path) -> Option<Encoding> {
+	let data = std::fs::read(dir.join("text/t_name._dt")).ok()?;
+	let data = if decompress::is_compressed(&data) {
+		decompress::decompress(&data).ok()?
+	} else {
+		data
+	};
+	let strings = data.split(|a| *a == 0)
+		.filter(|a| a.iter().all(|b| *b >= 0x20) && a.iter().any(|b| *b >= 0x80))
+		.collect::<Vec<_>>();
+	let is_sjis = |s: &[u8]| Encoding::Cp932.decode(s)
+		.is_ok_and(|s| !s.chars().any(|c| ('\u{FF61}'..='\u{FF9F}').contains(&c)));
+	if strings.iter().all(|s| is_sjis(s)) {
+		Some(Encoding::Cp932)
+	} else if strings.iter().all(|s| Encoding::Gbk.decode(s).is_ok()) {
+		Some(Encoding::Gbk)
+	} else {
+		None
+	}
+}
+
+fn unknown(path: &Path) -> Error {
+	Error::Unknown {
+		path: path.to_owned(),
+		backtrace: std::backtrace::Backtrace::capture().into(),
+	}
+}
+
+fn game_from_title(title: &str) -> Option<Game> {
+	if title.contains('零') {
+		Some(Game::Zero)
+	} else if title.contains('碧') {
+		Some(Game::Ao)
+	} else if title.contains("3rd") {
+		Some(Game::Tc)
+	} else if title.contains("SC") {
+		Some(Game::Sc)
+	} else if title.contains("FC") {
+		Some(Game::Fc)
+	} else {
+		None
+	}
+}
+
+/// Reads the `TITLE` field from a PSP or Vita `PARAM.SFO` file.
+fn sfo_title(path: &Path) -> Option<String> {
+	let data = std::fs::read(path).ok()?;
+	let u16_at = |p: usize| Some(u16::from_le_bytes(data.get(p..p+2)?.try_into().ok()?) as usize);
+	let u32_at = |p: usize| Some(u32::from_le_bytes(data.get(p..p+4)?.try_into().ok()?) as usize);
+	if !data.starts_with(b"\0PSF") {
+		return None
+	}
+	let keys = u32_at(8)?;
+	let values = u32_at(12)?;
+	for i in 0..u32_at(16)? {
+		let ent = 20 + i * 16;
+		let key = data.get(keys + u16_at(ent)?..)?;
+		let key = &key[..key.iter().position(|a| *a == 0)?];
+		if key == b"TITLE" {
+			let value = data.get(values + u32_at(ent+12)?..)?.get(..u32_at(ent+4)?)?;
+			let value = value.split(|a| *a == 0).next()?;
+			return String::from_utf8(value.to_owned()).ok()
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A `PARAM.SFO` with a `CATEGORY` field before the `TITLE`.
+	fn sfo(title: &str) -> Vec<u8> {
+		let keys = b"CATEGORY\0TITLE\0\0";
+		let mut values = b"UG\0\0".to_vec();
+		let title_off = values.len();
+		values.extend(title.as_bytes());
+		values.push(0);
+		let title_len = values.len() - title_off;
+		values.resize(title_len.next_multiple_of(4) + title_off, 0);
+
+		let mut out = Vec::new();
+		out.extend(b"\0PSF\x01\x01\0\0");
+		out.extend((20 + 2 * 16_u32).to_le_bytes());
+		out.extend((20 + 2 * 16 + keys.len() as u32).to_le_bytes());
+		out.extend(2u32.to_le_bytes());
+		for (key_off, len, data_off) in [(0, 3, 0), (9, title_len, title_off)] {
+			out.extend((key_off as u16).to_le_bytes());
+			out.extend([4, 2]);
+			out.extend((len as u32).to_le_bytes());
+			out.extend((len as u32).to_le_bytes());
+			out.extend((data_off as u32).to_le_bytes());
+		}
+		out.extend(keys);
+		out.extend(values);
+		out
+	}
+
+	fn tree(files: &[&str]) -> Result<tempfile::TempDir, Error> {
+		let tmp = tempfile::tempdir()?;
+		for file in files {
+			let path = tmp.path().join(file);
+			if let Some(dir) = file.strip_suffix('/') {
+				std::fs::create_dir_all(tmp.path().join(dir))?;
+			} else {
+				std::fs::create_dir_all(path.parent().unwrap())?;
+				std::fs::write(path, [])?;
+			}
+		}
+		Ok(tmp)
+	}
+
+	fn detect(tmp: &tempfile::TempDir) -> Option<(Game, Platform, Vec<PathBuf>, Option<&'static str>)> {
+		let install = Install::detect(tmp.path()).ok()?;
+		assert_eq!(install.path, tmp.path());
+		let dirs = install.dirs.iter().map(|a| a.strip_prefix(tmp.path()).unwrap().to_owned()).collect();
+		Some((install.game, install.platform, dirs, install.lang_suffix))
+	}
+
+	fn paths(a: &[&str]) -> Vec<PathBuf> {
+		a.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn sfo_titles() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		let path = tmp.path().join("PARAM.SFO");
+		std::fs::write(&path, sfo("英雄伝説 零の軌跡"))?;
+		assert_eq!(sfo_title(&path).as_deref(), Some("英雄伝説 零の軌跡"));
+
+		let mut data = sfo("英雄伝説 零の軌跡");
+		data.truncate(60);
+		std::fs::write(&path, data)?;
+		assert_eq!(sfo_title(&path), None);
+		std::fs::write(&path, b"\0PSX")?;
+		assert_eq!(sfo_title(&path), None);
+		assert_eq!(sfo_title(&tmp.path().join("missing")), None);
+		Ok(())
+	}
+
+	#[test]
+	fn psp() -> Result<(), Error> {
+		let tmp = tree(&["PSP_GAME/USRDIR/data/"])?;
+		std::fs::write(tmp.path().join("PSP_GAME/PARAM.SFO"), sfo("英雄伝説 碧の軌跡"))?;
+		assert_eq!(detect(&tmp), Some((Game::Ao, Platform::Psp, paths(&["PSP_GAME/USRDIR/data"]), None)));
+
+		// The Sky games on PSP are not supported
+		std::fs::write(tmp.path().join("PSP_GAME/PARAM.SFO"), sfo("英雄伝説 空の軌跡FC"))?;
+		assert_eq!(detect(&tmp), None);
+		Ok(())
+	}
+
+	#[test]
+	fn vita() -> Result<(), Error> {
+		let tmp = tree(&["gamedata/data/data/", "gamedata/data/data_sc/"])?;
+		assert_eq!(detect(&tmp), Some((Game::Sc, Platform::Vita, paths(&["gamedata/data/data_sc"]), None)));
+
+		let tmp = tree(&["data/data/", "data1/data/"])?;
+		assert_eq!(detect(&tmp), None);
+		std::fs::create_dir(tmp.path().join("sce_sys"))?;
+		std::fs::write(tmp.path().join("sce_sys/param.sfo"), sfo("英雄伝説 零の軌跡 Evolution"))?;
+		assert_eq!(detect(&tmp), Some((Game::Zero, Platform::Vita, paths(&["data/data", "data1/data"]), None)));
+		Ok(())
+	}
+
+	#[test]
+	fn ed7_pc() -> Result<(), Error> {
+		let tmp = tree(&["ED_ZERO.exe", "data/text_us/"])?;
+		assert_eq!(detect(&tmp), Some((Game::Zero, Platform::Nisa, paths(&["data"]), Some("_us"))));
+
+		let tmp = tree(&["ed_ao.exe", "data/", "data_en/"])?;
+		assert_eq!(detect(&tmp), Some((Game::Ao, Platform::Geofront, paths(&["data", "data_en"]), None)));
+
+		let tmp = tree(&["ed_ao_dx9.exe", "data/"])?;
+		assert_eq!(detect(&tmp), Some((Game::Ao, Platform::Pc, paths(&["data"]), None)));
+		// Could be either the Japanese or the Chinese release, and there are no names to tell
+		let mut install = Install::detect(tmp.path())?;
+		assert_eq!(install.encoding, None);
+		assert!(matches!(install.game_data(), Err(Error::NeedsEncoding { .. })));
+		install.encoding = Some(Encoding::Gbk);
+		assert_eq!(install.game_data()?.encoding, Encoding::Gbk);
+
+		// Not an executable
+		let tmp = tree(&["ed_zero.txt", "data/"])?;
+		assert_eq!(detect(&tmp), None);
+		Ok(())
+	}
+
+	#[test]
+	fn ed7_encoding() -> Result<(), Error> {
+		fn t_name(names: &[&[u8]]) -> Vec<u8> {
+			let mut data = Vec::new();
+			for name in names {
+				data.extend([0x10, 0, 0, 0]);
+				data.extend(*name);
+				data.push(0);
+			}
+			data
+		}
+		let tmp = tree(&["ed_ao.exe", "data/text/", "data_en/"])?;
+		let path = tmp.path().join("data/text/t_name._dt");
+		for (names, encoding) in [
+			// ロイド, エリィ
+			(&[&b"\x83\x8D\x83\x43\x83\x68"[..], b"\x83\x47\x83\x8A\x83\x42"][..], Some(Encoding::Cp932)),
+			// 罗伊德, 艾莉
+			(&[&b"\xC2\xDE\xD2\xC1\xB5\xC2"[..], b"\xB0\xAC\xC0\xF2"][..], Some(Encoding::Gbk)),
+			(&[&b"Lloyd"[..]][..], Some(Encoding::Cp932)),
+			(&[&b"\x83\x8D\x83"[..]][..], None),
+		] {
+			std::fs::write(&path, t_name(names))?;
+			assert_eq!(Install::detect(tmp.path())?.encoding, encoding, "{names:?}");
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn ed6_evo() -> Result<(), Error> {
+		let tmp = tree(&["gamedata/data/data/"])?;
+		let game = Install::detect(tmp.path())?.game_data()?;
+		assert_eq!(game.iset, InstructionSet::FcEvo);
+		assert_eq!(game.lookup.name(0x0001_0002).ok().as_deref(), Some("00010002"));
+		Ok(())
+	}
+
+	#[test]
+	fn ed6_pc() -> Result<(), Error> {
+		for (exe, game) in [("ED6_WIN.exe", Game::Fc), ("ed6_win2.exe", Game::Sc), ("ed6_win3_dx9.exe", Game::Tc)] {
+			let tmp = tree(&[exe, "ED6_DT00.dir"])?;
+			assert_eq!(detect(&tmp), Some((game, Platform::Pc, paths(&[""]), None)), "{exe}");
+		}
+
+		let tmp = tree(&["ed6_win.exe"])?;
+		assert_eq!(detect(&tmp), None);
+		Ok(())
+	}
+}
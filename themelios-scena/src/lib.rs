@@ -3,8 +3,10 @@
 #![feature(let_chains)]
 
 pub mod decompress;
+pub mod compress;
 pub mod gamedata;
 pub mod archive;
+pub mod vfs;
 pub mod tables;
 pub mod util;
 pub mod scena;
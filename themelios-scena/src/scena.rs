@@ -5,6 +5,8 @@ use crate::util::*;
 pub mod code;
 pub mod ed6;
 pub mod ed7;
+pub mod detect;
+pub mod translate;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[derive(derive_more::DebugCustom)]
@@ -246,19 +246,19 @@ themelios_macros::bytecode! {
 		///
 		/// I believe the CharId, which is only present in ED7, is used to select the textbox title.
 		/// However, it is 999 on chests.
-		TextMessage({ i if i.is_ed7() => u16, _ => const 255u16 } as CharId, text() -> Text), // [mes]
+		TextMessage({ i if i.is_ed7() => u16, _ => const 255u16 } as CharId, text(game) -> Text), // [mes]
 		skip!(1), // {asm} same as NextFrame
 		TextClose(u8), // [mes_close]
-		ScMenuSetTitle(u16, u16, u16, text() -> Text),
+		ScMenuSetTitle(u16, u16, u16, text(game) -> Text),
 		TextWait(), // [wait_prompt]
 		_59(), // Always directly after a TextReset 1, and exists in all but one such case. I suspect that one is a bug.
 		TextSetPos(i16, i16, i16, i16), // [mes_pos]
-		TextTalk(u16 as CharId, text() -> Text), // [popup]
-		TextTalkNamed(u16 as CharId, String alias TextTitle, text() -> Text), // [popup2]
-		Menu(u16 alias MenuId, i16, i16, u8, menu() -> Vec<String> alias Menu), // [menu] (the u8 is a bool)
+		TextTalk(u16 as CharId, text(game) -> Text), // [popup]
+		TextTalkNamed(u16 as CharId, string(game) -> String alias TextTitle, text(game) -> Text), // [popup2]
+		Menu(u16 alias MenuId, i16, i16, u8, menu(game) -> Vec<String> alias Menu), // [menu] (the u8 is a bool)
 		MenuWait(u16 as Var), // [wait_menu]
 		MenuClose(u16 alias MenuId), // [menu_close]
-		TextSetName(String alias TextTitle), // [name]
+		TextSetName(string(game) -> String alias TextTitle), // [name]
 		CharName2(u16 as CharId), // [name2]
 
 		Emote(u16 as CharId, i32, i32, emote() -> Emote, u8), // [emotion] mostly used through macros such as EMO_BIKKURI3(). Third argument is height.
@@ -306,10 +306,10 @@ themelios_macros::bytecode! {
 
 		#[game(Zero,ZeroEvo,Ao,AoEvo)] ED7_74(u8 as u16 alias ObjectId, u16),
 		#[game(Zero,ZeroEvo,Ao,AoEvo)] ED7_75(u8, u8, u32),
-		#[game(Zero,ZeroEvo,Ao,AoEvo)] ED7_76(u8 as u16 alias ObjectId, String, match {
+		#[game(Zero,ZeroEvo,Ao,AoEvo)] ED7_76(u8 as u16 alias ObjectId, string(game) -> String, match {
 			0 => _0(u32),
 			1 => _1(u32),
-			2 => _2(String),
+			2 => _2(string(game) -> String),
 			3 => _3(i32),
 			4 => _4(i32),
 		}),
@@ -332,7 +332,7 @@ themelios_macros::bytecode! {
 		#[game(Fc, FcEvo, Sc, ScEvo, Tc, TcEvo)] _7E(i16, i16, u16, u8, u32),
 
 		#[game(Zero, Ao, AoEvo)] ED7_84(u8, u8),
-		EffLoad(u8, String alias EffFileRef),
+		EffLoad(u8, string(game) -> String alias EffFileRef),
 		EffPlay(
 			u8 alias EffId, u8,
 			u16 as CharId, { i if i.is_ed7() => u16, _ => const 0u16 }, Pos3, // source
@@ -343,7 +343,7 @@ themelios_macros::bytecode! {
 		),
 		EffPlay2(
 			u8 alias EffId, u8,
-			u8 as u16 alias ObjectId, String, { i if i.is_ed7() => u16, _ => const 0u16 }, Pos3, // source
+			u8 as u16 alias ObjectId, string(game) -> String, { i if i.is_ed7() => u16, _ => const 0u16 }, Pos3, // source
 			i16, i16, i16,
 			u32, u32, u32, // scale
 			u32 alias Time, // period (0 if one-shot)
@@ -356,7 +356,7 @@ themelios_macros::bytecode! {
 
 		CharSetBase    (u16 as CharId, { i if i.is_ed7() => u8 as u16, _ => u16 } alias ChcpId), // [set_chr_base]
 		CharSetPattern (u16 as CharId, { i if i.is_ed7() => u8 as u16, _ => u16 }), // [set_chr_ptn]
-		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7CharSetName (u16 as CharId, String alias TextTitle), // debug script only
+		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7CharSetName (u16 as CharId, string(game) -> String alias TextTitle), // debug script only
 		CharSetPos     (u16 as CharId, Pos3, i16 alias Angle), // [set_pos]
 		CharSetPos2    (u16 as CharId, Pos3, i16 alias Angle),
 		CharLookAtChar (u16 as CharId, u16 as CharId, u16 as u32 alias Time), // [look_to]
@@ -466,7 +466,7 @@ themelios_macros::bytecode! {
 		/// What exactly this entails is unknown; the return value is never used.
 		#[game(Fc, FcEvo, Sc, ScEvo, Tc, TcEvo)] QuestSubmit(u8 as ShopId, u16 as QuestId),
 		#[game(Fc, FcEvo, Sc, ScEvo, Tc, TcEvo)] _ObjB0(u16 alias ObjectId, u8), // Used along with 6F, 70, and 73 during T0700#11
-		#[game(Fc, FcEvo, Sc, ScEvo, Tc, TcEvo)] OpLoad(String alias OpFileRef),
+		#[game(Fc, FcEvo, Sc, ScEvo, Tc, TcEvo)] OpLoad(string(game) -> String alias OpFileRef),
 
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7_B1(u8),
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] skip!(3),
@@ -477,7 +477,7 @@ themelios_macros::bytecode! {
 		}),
 
 		Video(match {
-			0 => Play(String alias AviFileRef, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }), // [movie(MOVIE_START)]
+			0 => Play(string(game) -> String alias AviFileRef, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }), // [movie(MOVIE_START)]
 			1 => End(u8, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }, { IS::Fc|IS::FcEvo => const 0u16, _ => u16 }), // [movie(MOVIE_END)], probably the 0 is the null terminator of an empty string
 		}),
 
@@ -558,13 +558,13 @@ themelios_macros::bytecode! {
 		}),
 
 		#[game(Fc)] skip!(3),
-		#[game(FcEvo, Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] VisLoad(u8 alias VisId, i16,i16,u16,u16, i16,i16,u16,u16, i16,i16,u16,u16, u32 as Color, u8, String),
+		#[game(FcEvo, Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] VisLoad(u8 alias VisId, i16,i16,u16,u16, i16,i16,u16,u16, i16,i16,u16,u16, u32 as Color, u8, string(game) -> String),
 		#[game(FcEvo, Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] VisColor(u8 alias VisId, u8, u32 as Color, u32 alias Time, u32, { IS::FcEvo|IS::Ao|IS::AoEvo => u32, _ => const 0u32 }),
 		#[game(FcEvo, Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] VisDispose(u8, u8 alias VisId, u8),
 
 		#[game(Fc,FcEvo)] skip!(19),
 
-		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_C8(u16, u16, String, u8, u16), // Something with C_PLATnn._CH
+		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_C8(u16, u16, string(game) -> String, u8, u16), // Something with C_PLATnn._CH
 		#[game(Zero, Ao, AoEvo)] ED7_CC(u8),
 		#[game(Zero, Ao, AoEvo)] ED7_CD(match {
 			0 => _0(u8),
@@ -579,13 +579,13 @@ themelios_macros::bytecode! {
 		#[game(Tc, TcEvo)] TcCharInSlot(u8, u8), // added team id I guess?
 		#[game(Sc, ScEvo, Tc, TcEvo)] ScSelect(match {
 			0 => New(u8 alias SelectId, u16, u16, u8),
-			1 => Add(u8 alias SelectId, String alias MenuItem),
+			1 => Add(u8 alias SelectId, string(game) -> String alias MenuItem),
 			2 => Show(u8 alias SelectId),
 			3 => SetDisabled(u8 alias SelectId, u8),
 		}),
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7Select(match {
 			0 => New(u8 alias SelectId),
-			1 => Add(u8 alias SelectId, String alias MenuItem),
+			1 => Add(u8 alias SelectId, string(game) -> String alias MenuItem),
 			2 => Show(u8 alias SelectId, u16, u16, u8),
 			3 => SetDisabled(u8 alias SelectId, u8),
 			4 => _4(u8 alias SelectId, u8),
@@ -594,7 +594,7 @@ themelios_macros::bytecode! {
 		}),
 		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_CD(u16 as CharId), // related to showing photographs
 		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Global(u8 as Global, expr(game) -> Expr),
-		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_CF(u16 as CharId, u8, String), // something with skeleton animation
+		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_CF(u16 as CharId, u8, string(game) -> String), // something with skeleton animation
 		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_D0(i32 alias Angle32, u32 alias Time),
 		#[game(Sc, ScEvo, Tc, TcEvo, Zero, ZeroEvo, Ao, AoEvo)] Sc_D1(u16 as CharId, i32, i32, i32, u32 alias Time), // something with camera?
 		#[game(Sc, ScEvo, Tc, TcEvo)] ED6LoadChcp(file_ref(game) -> String, file_ref(game) -> String, u8 as u16 alias ChcpId),
@@ -608,7 +608,7 @@ themelios_macros::bytecode! {
 		/// Always occurs before ObjSetFrame and ObjPlay. Probably animation speed?
 		#[game(Sc, ScEvo, Tc, TcEvo)] Sc_D8(u8 as u16 alias ObjectId, u16),
 		#[game(Sc, ScEvo, Tc, TcEvo)] ScCutIn(match {
-			0 => Show(String), // CTInnnnn
+			0 => Show(string(game) -> String), // CTInnnnn
 			1 => Hide(),
 		}),
 
@@ -618,7 +618,7 @@ themelios_macros::bytecode! {
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7_DA(u8),
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7_DB(),
 		#[game(Zero, ZeroEvo)] skip!(2),
-		#[game(Ao, AoEvo)] Ao_DE(String),
+		#[game(Ao, AoEvo)] Ao_DE(string(game) -> String),
 		#[game(Ao, AoEvo)] skip!(1),
 		#[game(Zero, ZeroEvo, Ao, AoEvo)] ED7_DE(u16),
 		#[game(Zero, Ao)] skip!(1),
@@ -693,12 +693,12 @@ themelios_macros::bytecode! {
 		/// Opens the save menu in order to save clear data.
 		SaveClearData(),
 
-		#[game(FcEvo, Sc, ScEvo, TcEvo)] Sc_DE(String), // a place name. Not a t_town, strangely
+		#[game(FcEvo, Sc, ScEvo, TcEvo)] Sc_DE(string(game) -> String), // a place name. Not a t_town, strangely
 		#[game(FcEvo, Sc, ScEvo, TcEvo)] skip!(1),
 		#[game(FcEvo, Sc, ScEvo, TcEvo)] Sc_E0(u8 as u16 alias ObjectId, Pos3),
 		#[game(FcEvo, Sc, ScEvo, TcEvo)] skip!(2),
 
-		#[game(FcEvo)] EvoCtp(String), // Refers to /data/map2/{}.ctp
+		#[game(FcEvo)] EvoCtp(string(game) -> String), // Refers to /data/map2/{}.ctp
 
 		#[game(Sc, ScEvo, TcEvo)] Sc_E3(u8, u16 as CharId, u8),
 		/// A no-op.
@@ -717,7 +717,7 @@ themelios_macros::bytecode! {
 				Ok(())
 			},
 		},
-		#[game(Sc, ScEvo)] Sc_E7(u8 as u16 alias ObjectId, String, u8,u8,u8,u8,u8),
+		#[game(Sc, ScEvo)] Sc_E7(u8 as u16 alias ObjectId, string(game) -> String, u8,u8,u8,u8,u8),
 		#[game(TcEvo)] skip!(1),
 		#[game(Sc, ScEvo, TcEvo)] Sc_E8(u32 alias Time),
 		#[game(Sc, ScEvo)] Sc_E9(u8), // related to RAM saving
@@ -741,7 +741,7 @@ themelios_macros::bytecode! {
 		#[game(TcEvo)] TcEvo_FC(u8, u8),
 
 		#[game(FcEvo, ScEvo, TcEvo)] EvoVoiceLine(u16), // [pop_msg]
-		#[game(FcEvo, ScEvo, TcEvo)] Evo_E6(text() -> Text),
+		#[game(FcEvo, ScEvo, TcEvo)] Evo_E6(text(game) -> Text),
 		#[game(FcEvo, ScEvo)] Evo_E7(u8 alias VisId, u8),
 		#[game(TcEvo)] skip!(1),
 
@@ -772,11 +772,11 @@ themelios_macros::bytecode! {
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E0(),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E1(u32,u32,u32,u32, u32,u32,u32,u32, u32,u32,u32,u32),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E2(u32,u32,u32, u8, u32, u32),
-		#[game(ZeroEvo, AoEvo)] ZeroEvo_E3(u8, String, u32, u32, u8),
+		#[game(ZeroEvo, AoEvo)] ZeroEvo_E3(u8, string(game) -> String, u32, u32, u8),
 		#[game(AoEvo)] AoEvo_E7(u32, u32 as Color, u32, u32),
 		#[game(AoEvo)] AoEvo_E8(u8),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E4(u8, u16),
-		#[game(ZeroEvo, AoEvo)] ZeroEvo_E5(u8, u8, String, String, Pos3, Pos3, Pos3, u8),
+		#[game(ZeroEvo, AoEvo)] ZeroEvo_E5(u8, u8, string(game) -> String, string(game) -> String, Pos3, Pos3, Pos3, u8),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E6(u8, u8, Pos3, Pos3, Pos3),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E7(u8, u8),
 		#[game(ZeroEvo, AoEvo)] ZeroEvo_E8(u8),
@@ -904,19 +904,30 @@ mod fork_loop {
 	}
 }
 
+mod string {
+	use super::*;
+	pub(super) fn read<'a>(f: &mut impl In<'a>, game: &GameData) -> Result<String, ReadError> {
+		f.string(&game.encoding)
+	}
+
+	pub(super) fn write(f: &mut impl Out, game: &GameData, v: &str) -> Result<(), WriteError> {
+		f.string(&game.encoding, v)
+	}
+}
+
 mod menu {
 	use super::*;
-	pub(super) fn read<'a>(f: &mut impl In<'a>) -> Result<Vec<String>, ReadError> {
-		Ok(f.string()?.split_terminator('\x01').map(|a| a.to_owned()).collect())
+	pub(super) fn read<'a>(f: &mut impl In<'a>, game: &GameData) -> Result<Vec<String>, ReadError> {
+		Ok(f.string(&game.encoding)?.split_terminator('\x01').map(|a| a.to_owned()).collect())
 	}
 
-	pub(super) fn write(f: &mut impl Out, v: &[String]) -> Result<(), WriteError> {
+	pub(super) fn write(f: &mut impl Out, game: &GameData, v: &[String]) -> Result<(), WriteError> {
 		let mut s = String::new();
 		for line in v {
 			s.push_str(line.as_str());
 			s.push('\x01');
 		}
-		f.string(&s)?;
+		f.string(&game.encoding, &s)?;
 		Ok(())
 	}
 }
@@ -1005,12 +1016,12 @@ mod func_ref_u8_u16 {
 
 mod text {
 	use super::*;
-	pub(super) fn read<'a>(f: &mut impl In<'a>) -> Result<Text, ReadError> {
-		crate::text::Text::read(f)
+	pub(super) fn read<'a>(f: &mut impl In<'a>, game: &GameData) -> Result<Text, ReadError> {
+		crate::text::Text::read(f, game)
 	}
 
-	pub(super) fn write(f: &mut impl Out, v: &Text) -> Result<(), WriteError> {
-		crate::text::Text::write(f, v)
+	pub(super) fn write(f: &mut impl Out, game: &GameData, v: &Text) -> Result<(), WriteError> {
+		crate::text::Text::write(f, game, v)
 	}
 }
 
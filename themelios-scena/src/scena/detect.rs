@@ -0,0 +1,154 @@
+//! Guessing which game a scena file belongs to.
+//!
+//! This is done by simply trying to parse it with every instruction set, and checking how well it
+//! survives being written back.
+
+use std::panic::AssertUnwindSafe;
+
+use crate::gamedata::{GameData, IndexLookup, LookupRef};
+use crate::util::Encoding;
+use super::code::InstructionSet;
+
+/// How well a scena file matched an instruction set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Evidence {
+	/// Could not be parsed, or the parser or writer panicked.
+	Failed { error: String },
+	/// Parsed, but could not be written back.
+	Unwritable { error: String },
+	/// Parsed and written back, but with differences. `prefix` is the number of bytes at the start
+	/// that are the same in both, and `len` is the length of the longer one.
+	///
+	/// Only the common prefix counts, since a single misparsed instruction shifts everything after
+	/// it, and the bytes that happen to line up after that say nothing about the match.
+	Written { prefix: usize, len: usize },
+	/// Written back to exactly the same bytes.
+	Exact,
+}
+
+impl Evidence {
+	/// A score from 0 to 1, with 1 being a perfect match.
+	pub fn score(&self) -> f32 {
+		match self {
+			Evidence::Failed { .. } => 0.0,
+			Evidence::Unwritable { .. } => 0.25,
+			Evidence::Written { prefix, len } => 0.5 + 0.4 * *prefix as f32 / (*len).max(1) as f32,
+			Evidence::Exact => 1.0,
+		}
+	}
+}
+
+impl std::fmt::Display for Evidence {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Evidence::Failed { error } => write!(f, "failed to parse: {}", error.lines().next().unwrap_or_default()),
+			Evidence::Unwritable { error } => write!(f, "parsed, but failed to write: {}", error.lines().next().unwrap_or_default()),
+			Evidence::Written { prefix, len } => write!(f, "parsed, first {prefix}/{len} bytes identical after rewriting"),
+			Evidence::Exact => write!(f, "roundtrips exactly"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+	pub iset: InstructionSet,
+	pub kai: bool,
+	pub evidence: Evidence,
+}
+
+impl std::fmt::Display for Candidate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{:?}{}: {}", self.iset, if self.kai { " (kai)" } else { "" }, self.evidence)
+	}
+}
+
+const CANDIDATES: &[(InstructionSet, bool)] = &[
+	(InstructionSet::Fc, false),
+	(InstructionSet::FcEvo, false),
+	(InstructionSet::Sc, false),
+	(InstructionSet::ScEvo, false),
+	(InstructionSet::Tc, false),
+	(InstructionSet::TcEvo, false),
+	(InstructionSet::Zero, false),
+	(InstructionSet::Zero, true),
+	(InstructionSet::ZeroEvo, false),
+	(InstructionSet::Ao, false),
+	(InstructionSet::Ao, true),
+	(InstructionSet::AoEvo, false),
+];
+
+/// Tries parsing an uncompressed scena file with every instruction set.
+///
+/// Returns all candidates, best first. Several candidates may match equally well, since many
+/// files only use instructions that are the same in several games.
+///
+/// Parsing with the wrong instruction set can run into panics in the reader or writer; these are
+/// caught and count as [`Evidence::Failed`]. The panic message is still printed by the panic hook.
+pub fn detect(data: &[u8]) -> Vec<Candidate> {
+	let mut candidates = CANDIDATES.iter().map(|&(iset, kai)| {
+		let game = GameData { iset, lookup: LookupRef::Borrowed(&IndexLookup), kai, encoding: Encoding::Cp932, geofront: false };
+		let evidence = std::panic::catch_unwind(AssertUnwindSafe(|| check(&game, data)))
+			.unwrap_or_else(|payload| Evidence::Failed { error: panic_message(&*payload) });
+		Candidate { iset, kai, evidence }
+	}).collect::<Vec<_>>();
+	candidates.sort_by(|a, b| b.evidence.score().total_cmp(&a.evidence.score()));
+	candidates
+}
+
+fn check(game: &GameData, data: &[u8]) -> Evidence {
+	let written = if game.iset.is_ed7() {
+		match super::ed7::read(game, data) {
+			Ok(scena) => super::ed7::write(game, &scena),
+			Err(e) => return Evidence::Failed { error: e.to_string() },
+		}
+	} else {
+		match super::ed6::read(game, data) {
+			Ok(scena) => super::ed6::write(game, &scena),
+			Err(e) => return Evidence::Failed { error: e.to_string() },
+		}
+	};
+	match written {
+		Ok(data2) if data2 == data => Evidence::Exact,
+		Ok(data2) => Evidence::Written {
+			prefix: data.iter().zip(&data2).take_while(|(a, b)| a == b).count(),
+			len: data.len().max(data2.len()),
+		},
+		Err(e) => Evidence::Unwritable { error: e.to_string() },
+	}
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+	let msg = payload.downcast_ref::<&str>().copied()
+		.or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+		.unwrap_or("unknown payload");
+	format!("panicked: {msg}")
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn garbage() {
+		let candidates = detect(&[0xFF; 64]);
+		assert_eq!(candidates.len(), CANDIDATES.len());
+		assert!(candidates.iter().all(|a| a.evidence != Evidence::Exact), "{candidates:#?}");
+	}
+
+	#[test]
+	fn panic_messages() {
+		let payload = std::panic::catch_unwind(|| panic!("oops {}", 1)).unwrap_err();
+		assert_eq!(panic_message(&*payload), "panicked: oops 1");
+		let payload = std::panic::catch_unwind(|| panic!("oops")).unwrap_err();
+		assert_eq!(panic_message(&*payload), "panicked: oops");
+	}
+
+	#[test]
+	fn score() {
+		let a = Evidence::Written { prefix: 90, len: 100 };
+		let b = Evidence::Written { prefix: 10, len: 100 };
+		assert!(a.score() > b.score());
+		assert!(Evidence::Exact.score() > a.score());
+		assert!(b.score() > Evidence::Unwritable { error: String::new() }.score());
+	}
+}
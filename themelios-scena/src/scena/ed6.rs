@@ -29,6 +29,73 @@ pub struct Scena {
 	pub look_points: Vec<LookPoint>,
 	pub entries: Vec<Entry>,
 	pub functions: Vec<Vec<code::FlatInsn>>,
+	/// `None` if the parts of the file overlap or do not start right after the header. That cannot
+	/// be reproduced, so such files are written with the standard layout.
+	pub layout: Option<Layout>,
+}
+
+/// A part of the file that is referred to from the header, for [`Layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Part {
+	Ch,
+	Cp,
+	Npcs,
+	Monsters,
+	Triggers,
+	LookPoints,
+	Code,
+	FuncTable,
+	Strings,
+}
+
+/// The order of the parts of a file, each followed by any bytes between it and the next one.
+///
+/// Falcom's files all use [`Layout::standard`], which is represented by an empty layout. Files
+/// rebuilt by other tools, such as the voice patches, may arrange things differently; [`read`]
+/// records that so that [`write`] can reproduce them exactly.
+///
+/// Calmare does not write the layout, so files with a nonstandard layout lose their padding when
+/// they go through it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Layout(pub Vec<(Part, Vec<u8>)>);
+
+impl Layout {
+	pub fn standard() -> Layout {
+		Layout(vec![
+			(Part::Ch, vec![0xFF]),
+			(Part::Cp, vec![0xFF]),
+			(Part::Npcs, vec![]),
+			(Part::Monsters, vec![]),
+			(Part::Triggers, vec![]),
+			(Part::LookPoints, vec![]),
+			(Part::Code, vec![]),
+			(Part::FuncTable, vec![]),
+			(Part::Strings, vec![]),
+		])
+	}
+
+	/// Finds the layout from the `(part, start, end)` of each part, given in standard order.
+	///
+	/// Returns `None` if the parts overlap or do not start right after the header, since that
+	/// cannot be reproduced.
+	fn find(data: &[u8], head_end: usize, mut parts: Vec<(Part, usize, usize)>) -> Option<Layout> {
+		// Stable sort, so empty parts stay in standard order
+		parts.sort_by_key(|a| a.1);
+		if parts.first().map(|a| a.1) != Some(head_end) {
+			return None
+		}
+		let mut layout = Vec::with_capacity(parts.len());
+		for (i, &(part, _, end)) in parts.iter().enumerate() {
+			let next = parts.get(i + 1).map_or(data.len(), |a| a.1);
+			layout.push((part, data.get(end..next)?.to_owned()));
+		}
+		let layout = Layout(layout);
+		if layout == Layout::standard() {
+			Some(Layout::default())
+		} else {
+			Some(layout)
+		}
+	}
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -102,8 +169,8 @@ pub struct LookPoint { // [LookPoint]
 pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 	let mut f = Coverage::new(Bytes::new(data));
 
-	let path = f.sized_string::<10>()?;
-	let map = f.sized_string::<14>()?;
+	let path = f.sized_string::<10>(&game.encoding)?;
+	let map = f.sized_string::<14>(&game.encoding)?;
 	let town = TownId(f.u16()?);
 	let bgm = BgmId(f.u16()?);
 	let item = FuncRef(f.u16()?, f.u16()?);
@@ -120,23 +187,31 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 	let look_points = (f.ptr()?, f.u16()?);
 
 	let mut strings = f.ptr()?;
+	let strings_start = strings.pos();
 
 	let code_start = f.u16()? as usize;
 	f.check_u16(0)?;
 	let code_end = f.clone().u16()? as usize;
 	let func_table = (f.ptr()?, f.u16()? / 2);
 
-	ensure!(strings.string()? == "@FileName", "expected @FileName");
+	let mut parts = Vec::with_capacity(9);
+
+	ensure!(strings.string(&game.encoding)? == "@FileName", "expected @FileName");
 
 	let (mut g, n) = ch;
+	let start = g.pos();
 	let ch = list(n as usize, || Ok(game.lookup.name(g.u32()?)?)).strict()?;
+	parts.push((Part::Ch, start, g.pos()));
 
 	let (mut g, n) = cp;
+	let start = g.pos();
 	let cp = list(n as usize, || Ok(game.lookup.name(g.u32()?)?)).strict()?;
+	parts.push((Part::Cp, start, g.pos()));
 
 	let (mut g, n) = npcs;
+	let start = g.pos();
 	let npcs = list(n as usize, || Ok(Npc {
-		name: strings.string()?,
+		name: strings.string(&game.encoding)?,
 		pos: g.pos3()?,
 		angle: g.i16()?,
 		x: g.u16()?,
@@ -147,10 +222,12 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		init: FuncRef(g.u16()?, g.u16()?),
 		talk: FuncRef(g.u16()?, g.u16()?),
 	})).strict()?;
+	parts.push((Part::Npcs, start, g.pos()));
 
 	let (mut g, n) = monsters;
+	let start = g.pos();
 	let monsters = list(n as usize, || Ok(Monster {
-		name: strings.string()?,
+		name: strings.string(&game.encoding)?,
 		pos: g.pos3()?,
 		angle: g.i16()?,
 		unk1: g.u16()?,
@@ -160,8 +237,10 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		flag: Flag(g.u16()?),
 		unk3: g.u16()?,
 	})).strict()?;
+	parts.push((Part::Monsters, start, g.pos()));
 
 	let (mut g, n) = triggers;
+	let start = g.pos();
 	let triggers = list(n as usize, || Ok(Trigger {
 		pos1: g.pos3()?,
 		pos2: g.pos3()?,
@@ -169,8 +248,10 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		func: FuncRef(g.u16()?, g.u16()?),
 		unk1: g.u16()?,
 	})).strict()?;
+	parts.push((Part::Triggers, start, g.pos()));
 
 	let (mut g, n) = look_points;
+	let start = g.pos();
 	let look_points = list(n as usize, || Ok(LookPoint {
 		pos: g.pos3()?,
 		radius: g.u32()?,
@@ -179,9 +260,14 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		func: FuncRef(g.u16()?, g.u16()?),
 		unk1: g.u16()?,
 	})).strict()?;
+	parts.push((Part::LookPoints, start, g.pos()));
+
+	parts.push((Part::Code, code_start, code_end));
 
 	let (mut g, n) = func_table;
+	let start = g.pos();
 	let func_table = list(n as usize, || Ok(g.u16()? as usize)).strict()?;
+	parts.push((Part::FuncTable, start, g.pos()));
 	ensure!(func_table.is_empty() || func_table[0] == code_start,
 		"Unexpected func table: {func_table:X?} does not start with {code_start:X?}"
 	);
@@ -215,6 +301,9 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		functions.push(code::read(&mut f.clone().at(start)?, game, Some(end))?);
 	}
 
+	parts.push((Part::Strings, strings_start, strings.pos()));
+	let layout = Layout::find(data, head_end, parts);
+
 	Ok(Scena {
 		path, map,
 		town, bgm,
@@ -225,6 +314,7 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 		triggers, look_points,
 		entries,
 		functions,
+		layout,
 	})
 }
 
@@ -244,14 +334,21 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		ref look_points,
 		ref entries,
 		ref functions,
+		ref layout,
 	} = scena;
 	let mut f = OutBytes::new();
-	let mut g = OutBytes::new();
+	let mut g_ch = OutBytes::new();
+	let mut g_cp = OutBytes::new();
+	let mut g_npcs = OutBytes::new();
+	let mut g_monsters = OutBytes::new();
+	let mut g_triggers = OutBytes::new();
+	let mut g_look_points = OutBytes::new();
+	let mut code = OutBytes::new();
 	let mut func_table = OutBytes::new();
 	let mut strings = OutBytes::new();
 
-	f.sized_string::<10>(path)?;
-	f.sized_string::<14>(map)?;
+	f.sized_string::<10>(&game.encoding, path)?;
+	f.sized_string::<14>(&game.encoding, map)?;
 	f.u16(town.0);
 	f.u16(bgm.0);
 	f.u16(item.0); f.u16(item.1);
@@ -283,7 +380,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 	f.u16(cast(look_points.len())?);
 
 	f.delay_u16(strings.here());
-	strings.string("@FileName")?;
+	strings.string(&game.encoding, "@FileName")?;
 
 	let (l_code_start, l_code_start_) = Label::new();
 	f.delay_u16(l_code_start);
@@ -292,17 +389,18 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 	f.delay_u16(l_func_table);
 	f.u16(cast(functions.len() * 2)?);
 
+	let g = &mut g_ch;
 	g.label(l_ch_);
 	for ch in ch { g.u32(game.lookup.index(ch)?); }
-	g.u8(0xFF);
 
+	let g = &mut g_cp;
 	g.label(l_cp_);
 	for cp in cp { g.u32(game.lookup.index(cp)?); }
-	g.u8(0xFF);
 
+	let g = &mut g_npcs;
 	g.label(l_npcs_);
 	for &Npc { ref name, pos, angle, x, cp, frame, ch, flags, init, talk } in npcs {
-		strings.string(name)?;
+		strings.string(&game.encoding, name)?;
 		g.pos3(pos);
 		g.i16(angle);
 		g.u16(x);
@@ -314,9 +412,10 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		g.u16(talk.0); g.u16(talk.1);
 	}
 
+	let g = &mut g_monsters;
 	g.label(l_monsters_);
 	for &Monster { ref name, pos, angle, unk1, flags, unk2, battle, flag, unk3 } in monsters {
-		strings.string(name)?;
+		strings.string(&game.encoding, name)?;
 		g.pos3(pos);
 		g.i16(angle);
 		g.u16(unk1);
@@ -327,6 +426,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		g.u16(unk3);
 	}
 
+	let g = &mut g_triggers;
 	g.label(l_triggers_);
 	for &Trigger { pos1, pos2, flags, func, unk1 } in triggers {
 		g.pos3(pos1);
@@ -336,6 +436,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		g.u16(unk1);
 	}
 
+	let g = &mut g_look_points;
 	g.label(l_look_points_);
 	for &LookPoint { pos, radius, bubble_pos, flags, func, unk1 } in look_points {
 		g.pos3(pos);
@@ -347,10 +448,10 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 	}
 
 	func_table.label(l_func_table_);
-	g.label(l_code_start_);
+	code.label(l_code_start_);
 	for func in functions.iter() {
-		func_table.delay_u16(g.here());
-		code::write(&mut g, game, func)?;
+		func_table.delay_u16(code.here());
+		code::write(&mut code, game, func)?;
 	}
 
 	for &Entry {
@@ -375,5 +476,36 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		f.u16(reinit.0); f.u16(reinit.1);
 	}
 
-	Ok(f.concat(g).concat(func_table).concat(strings).finish()?)
+	let mut parts = vec![
+		(Part::Ch, g_ch),
+		(Part::Cp, g_cp),
+		(Part::Npcs, g_npcs),
+		(Part::Monsters, g_monsters),
+		(Part::Triggers, g_triggers),
+		(Part::LookPoints, g_look_points),
+		(Part::Code, code),
+		(Part::FuncTable, func_table),
+		(Part::Strings, strings),
+	];
+	let standard;
+	let layout = match layout {
+		Some(layout) if !layout.0.is_empty() => layout,
+		_ => {
+			standard = Layout::standard();
+			&standard
+		}
+	};
+	for (part, pad) in &layout.0 {
+		let Some(i) = parts.iter().position(|a| a.0 == *part) else {
+			bail!("{part:?} appears twice in layout")
+		};
+		let (_, mut g) = parts.remove(i);
+		g.slice(pad);
+		f = f.concat(g);
+	}
+	if let Some((part, _)) = parts.first() {
+		bail!("{part:?} is missing from layout")
+	}
+
+	Ok(f.finish()?)
 }
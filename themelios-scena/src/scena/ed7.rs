@@ -158,8 +158,8 @@ pub struct BattleSetup {
 pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 	let mut f = Bytes::new(data);
 
-	let name1 = f.sized_string::<10>()?;
-	let name2 = f.sized_string::<10>()?;
+	let name1 = f.sized_string::<10>(&game.encoding)?;
+	let name2 = f.sized_string::<10>(&game.encoding)?;
 	let town = TownId(f.u16()?);
 	let bgm = BgmId(f.u16()?);
 	let flags = f.u32()?;
@@ -167,7 +167,7 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 
 	let mut strings = f.ptr32()?;
 	let strings_start = strings.pos();
-	let filename = strings.string()?;
+	let filename = strings.string(&game.encoding)?;
 
 	let p_chcp     = f.u16()? as usize;
 	let p_npcs     = f.u16()? as usize;
@@ -256,7 +256,7 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 
 	let mut g = f.clone().at(p_npcs)?;
 	let npcs = list(n_npcs, || Ok(Npc {
-		name: strings.string()?,
+		name: strings.string(&game.encoding)?,
 		pos: g.pos3()?,
 		angle: g.i16()?,
 		unk1: g.u16()?,
@@ -355,7 +355,7 @@ pub fn read(game: &GameData, data: &[u8]) -> Result<Scena, ReadError> {
 			pos: (g.f32()?, g.f32()?, g.f32()?),
 			unk1: g.u16()?,
 			unk2: g.u16()?,
-			name: g.ptr32()?.string()?,
+			name: g.ptr32()?.string(&game.encoding)?,
 		})).strict()?)
 	};
 
@@ -498,7 +498,7 @@ impl BattleRead {
 					can_move: f.u8()?,
 					move_speed: f.u16()?,
 					unk2: f.u16()?,
-					battlefield: f.ptr32()?.string()?,
+					battlefield: f.ptr32()?.string(&game.encoding)?,
 					sepith: match f.u32()? {
 						0 => None,
 						n => Some(self.get_sepith(&mut f.clone().at(n as usize)?)?)
@@ -534,15 +534,15 @@ impl BattleRead {
 
 pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
-	f.sized_string::<10>(&scena.name1)?;
-	f.sized_string::<10>(&scena.name2)?;
+	f.sized_string::<10>(&game.encoding, &scena.name1)?;
+	f.sized_string::<10>(&game.encoding, &scena.name2)?;
 	f.u16(scena.town.0);
 	f.u16(scena.bgm.0);
 	f.u32(scena.flags);
 	f.multiple_loose::<6, _>(&[0xFF; 4], &scena.includes, |g, a| { g.u32(game.lookup.index(a)?); Ok(()) }).strict()?;
 
 	let mut strings = f.ptr32();
-	strings.string(&scena.filename)?;
+	strings.string(&game.encoding, &scena.filename)?;
 
 	let mut chcp = f.ptr();
 	let mut npcs = f.ptr();
@@ -586,7 +586,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 
 	let g = &mut npcs;
 	for npc in &scena.npcs {
-		strings.string(&npc.name)?;
+		strings.string(&game.encoding, &npc.name)?;
 		g.pos3(npc.pos);
 		g.i16(npc.angle);
 		g.u16(npc.unk1);
@@ -719,7 +719,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 		g.u16(battle.move_speed);
 		g.u16(battle.unk2);
 		g.delay_u32(strings.here());
-		strings.string(&battle.battlefield)?;
+		strings.string(&game.encoding, &battle.battlefield)?;
 		if let Some(s) = battle.sepith {
 			g.delay_u32(field_sepith_pos.get(s as usize).cloned()
 				.ok_or_else(|| "field sepith out of bounds".to_owned())?);
@@ -756,7 +756,7 @@ pub fn write(game: &GameData, scena: &Scena) -> Result<Vec<u8>, WriteError> {
 			g.u16(l.unk1);
 			g.u16(l.unk2);
 			g.delay_u32(strings.here());
-			strings.string(&l.name)?;
+			strings.string(&game.encoding, &l.name)?;
 		}
 	}
 
@@ -0,0 +1,241 @@
+//! Extracting the translatable strings of scena files, and putting translations back.
+//!
+//! Each string has an id made of the file name and where in the file it is: `t0100:3:17:1` is
+//! argument 1 of instruction 17 in function 3, `t0100:3:20:4:2` is item 2 of the menu in argument
+//! 4, and `t0100:npc:2` is the name of the third npc. Ids stay valid as long as the structure of the
+//! code is not changed.
+
+use std::collections::BTreeMap;
+
+use crate::gamedata::GameData;
+use crate::text::{Text, TextSegment, MarkupError, Code, control_code};
+use crate::util::EncodeError;
+use super::{ed6, ed7};
+use super::code::{FlatInsn, Insn, InsnArg, InsnArgMut, InstructionSet};
+
+type Backtrace = Box<std::backtrace::Backtrace>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("{id}: {source}")]
+	Markup { id: String, source: MarkupError, backtrace: Backtrace },
+
+	#[error("{id}: {source}")]
+	Encode { id: String, source: EncodeError, backtrace: Backtrace },
+
+	#[error("{id}: invalid text control code 0x{code:02X} for {iset:?}")]
+	ControlCode { id: String, code: u8, iset: InstructionSet, backtrace: Backtrace },
+
+	#[error("{id}: no such string")]
+	Unknown { id: String, backtrace: Backtrace },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	pub id: String,
+	/// Name of the npc who says it, if known.
+	pub speaker: Option<String>,
+	/// The instruction the string is in, or `npc` for names.
+	pub context: &'static str,
+	/// The string itself. [`Text`] is written as markup, see its `Display` impl.
+	pub text: String,
+}
+
+pub fn extract_ed6(game: &GameData, file: &str, scena: &ed6::Scena) -> Vec<Entry> {
+	let npcs = scena.npcs.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+	extract(game, file, &npcs, &scena.functions)
+}
+
+pub fn extract_ed7(game: &GameData, file: &str, scena: &ed7::Scena) -> Vec<Entry> {
+	let npcs = scena.npcs.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+	extract(game, file, &npcs, &scena.functions)
+}
+
+/// Replaces the strings that have a translation in `translations`, keyed by id.
+///
+/// Ids that belong to other files are ignored. Translations that cannot be parsed or encoded are
+/// reported and left untranslated, as are ones with control codes the game does not use, so the
+/// scena can always be written afterwards.
+pub fn apply_ed6(game: &GameData, file: &str, scena: &mut ed6::Scena, translations: &BTreeMap<String, String>) -> Vec<Error> {
+	let npcs = scena.npcs.iter_mut().map(|a| &mut a.name).collect::<Vec<_>>();
+	apply(game, file, npcs, &mut scena.functions, translations)
+}
+
+pub fn apply_ed7(game: &GameData, file: &str, scena: &mut ed7::Scena, translations: &BTreeMap<String, String>) -> Vec<Error> {
+	let npcs = scena.npcs.iter_mut().map(|a| &mut a.name).collect::<Vec<_>>();
+	apply(game, file, npcs, &mut scena.functions, translations)
+}
+
+fn extract(game: &GameData, file: &str, npcs: &[&str], functions: &[Vec<FlatInsn>]) -> Vec<Entry> {
+	let mut out = Vec::new();
+	for (n, name) in npcs.iter().enumerate() {
+		out.push(Entry {
+			id: format!("{file}:npc:{n}"),
+			speaker: None,
+			context: "npc",
+			text: name.to_string(),
+		});
+	}
+
+	for (i, func) in functions.iter().enumerate() {
+		for (j, insn) in func.iter().enumerate() {
+			let FlatInsn::Insn(insn) = insn else { continue };
+			let speaker = speaker(game, npcs, insn);
+			let mut push = |id: String, text: String| out.push(Entry {
+				id,
+				speaker: speaker.clone(),
+				context: insn.name(),
+				text,
+			});
+			for (k, arg) in insn.args().iter().enumerate() {
+				let id = format!("{file}:{i}:{j}:{k}");
+				match arg {
+					InsnArg::Text(t) => push(id, t.to_string()),
+					InsnArg::TextTitle(s) | InsnArg::MenuItem(s) => push(id, s.to_string()),
+					InsnArg::Menu(items) => {
+						for (l, s) in items.iter().enumerate() {
+							push(format!("{id}:{l}"), s.clone());
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+	}
+	out
+}
+
+/// The npc referred to by the instruction's [`CharId`](super::CharId), if any.
+///
+/// Npcs are numbered from 8 in all games, but the ids at the top are used for party members in a
+/// few of them; these use the same ranges as calmare.
+fn speaker(game: &GameData, npcs: &[&str], insn: &Insn) -> Option<String> {
+	let char = insn.args().iter().find_map(|a| match a {
+		InsnArg::CharId(c) => Some(c.0),
+		_ => None,
+	})?;
+	let npc = match char {
+		254.. => return None,
+		238.. if matches!(game.iset, InstructionSet::Tc | InstructionSet::TcEvo) => return None,
+		248.. if matches!(game.iset, InstructionSet::Sc | InstructionSet::ScEvo) => return None,
+		8.. => char - 8,
+		_ => return None,
+	};
+	Some(npcs.get(npc as usize)?.to_string())
+}
+
+enum Target<'a> {
+	Text(&'a mut Text),
+	String(&'a mut String),
+}
+
+fn apply(
+	game: &GameData,
+	file: &str,
+	npcs: Vec<&mut String>,
+	functions: &mut [Vec<FlatInsn>],
+	translations: &BTreeMap<String, String>,
+) -> Vec<Error> {
+	let mut targets = BTreeMap::new();
+	for (n, name) in npcs.into_iter().enumerate() {
+		targets.insert(format!("{file}:npc:{n}"), Target::String(name));
+	}
+	for (i, func) in functions.iter_mut().enumerate() {
+		for (j, insn) in func.iter_mut().enumerate() {
+			let FlatInsn::Insn(insn) = insn else { continue };
+			for (k, arg) in insn.args_mut().into_vec().into_iter().enumerate() {
+				let id = format!("{file}:{i}:{j}:{k}");
+				match arg {
+					InsnArgMut::Text(t) => { targets.insert(id, Target::Text(t)); }
+					InsnArgMut::TextTitle(s) | InsnArgMut::MenuItem(s) => { targets.insert(id, Target::String(s)); }
+					InsnArgMut::Menu(items) => {
+						for (l, s) in items.iter_mut().enumerate() {
+							targets.insert(format!("{id}:{l}"), Target::String(s));
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+	}
+
+	let prefix = format!("{file}:");
+	let mut errors = Vec::new();
+	for (id, text) in translations.range(prefix.clone()..).take_while(|a| a.0.starts_with(&prefix)) {
+		let result = match targets.get_mut(id) {
+			Some(Target::Text(t)) => apply_text(game, id, t, text),
+			Some(Target::String(s)) => match game.encoding.encode(text) {
+				Ok(_) => { **s = text.clone(); Ok(()) }
+				Err(source) => Err(Error::Encode {
+					id: id.clone(),
+					source,
+					backtrace: std::backtrace::Backtrace::capture().into(),
+				}),
+			},
+			None => Err(Error::Unknown {
+				id: id.clone(),
+				backtrace: std::backtrace::Backtrace::capture().into(),
+			}),
+		};
+		errors.extend(result.err());
+	}
+	errors
+}
+
+fn apply_text(game: &GameData, id: &str, target: &mut Text, text: &str) -> Result<(), Error> {
+	let text = text.parse::<Text>().map_err(|source| Error::Markup {
+		id: id.to_owned(),
+		source,
+		backtrace: std::backtrace::Backtrace::capture().into(),
+	})?;
+	for item in text.iter() {
+		match item {
+			TextSegment::String(s) => {
+				game.encoding.encode(s).map_err(|source| Error::Encode {
+					id: id.to_owned(),
+					source,
+					backtrace: std::backtrace::Backtrace::capture().into(),
+				})?;
+			}
+			TextSegment::Byte(n) if control_code(game, *n) != Some(Code::Unknown) => {
+				return Err(Error::ControlCode {
+					id: id.to_owned(),
+					code: *n,
+					iset: game.iset,
+					backtrace: std::backtrace::Backtrace::capture().into(),
+				})
+			}
+			_ => {}
+		}
+	}
+	*target = text;
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn control_codes() {
+		let original = Text(vec![TextSegment::String("a".to_owned())]);
+		for (game, markup, ok) in [
+			(GameData::ZERO, "a{0x05}b", true),
+			(GameData::ZERO, "a{0x0A}b", false),
+			(GameData::ZERO, "a{0x01}b", false),
+			(GameData::AO, "a{0x0A}b", false),
+			(GameData::AO_GF, "a{0x0A}b", true),
+			(GameData::AO_EVO, "a{0x0C}b", false),
+		] {
+			let mut target = original.clone();
+			let result = apply_text(game, "t0100:0:0:0", &mut target, markup);
+			assert_eq!(result.is_ok(), ok, "{:?} {markup}", game.iset);
+			if ok {
+				assert_eq!(target.to_string(), markup);
+			} else {
+				assert!(matches!(result, Err(Error::ControlCode { .. })), "{:?} {markup}", game.iset);
+				assert_eq!(target, original);
+			}
+		}
+	}
+}
@@ -3,6 +3,7 @@ use std::collections::BTreeMap;
 use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
 use hamu::write::le::*;
+use crate::gamedata::GameData;
 use crate::util::*;
 
 newtype!(BgmId, u16);
@@ -14,13 +15,13 @@ pub struct Bgm {
 }
 
 // I'm throwing away the record order in the file here, hope that doesn't matter.
-pub fn read(t_town: &[u8]) -> Result<BTreeMap<BgmId, Bgm>, ReadError> {
+pub fn read(game: &GameData, t_town: &[u8]) -> Result<BTreeMap<BgmId, Bgm>, ReadError> {
 	let mut f = Coverage::new(Bytes::new(t_town));
 	let mut table = BTreeMap::new();
 	while f.remaining() > 0 {
 		let id = BgmId(f.u16()?);
 		f.check_u16(0)?;
-		let name = f.sized_string::<8>()?;
+		let name = f.sized_string::<8>(&game.encoding)?;
 		let loops = cast_bool(f.u32()?)?;
 		table.insert(id, Bgm { name, loops });
 	}
@@ -29,12 +30,12 @@ pub fn read(t_town: &[u8]) -> Result<BTreeMap<BgmId, Bgm>, ReadError> {
 	Ok(table)
 }
 
-pub fn write(table: &BTreeMap<BgmId, Bgm>) -> Result<Vec<u8>, WriteError> {
+pub fn write(game: &GameData, table: &BTreeMap<BgmId, Bgm>) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	for (&id, &Bgm { ref name, loops }) in table {
 		f.u16(id.0);
 		f.u16(0);
-		f.sized_string::<8>(name)?;
+		f.sized_string::<8>(&game.encoding, name)?;
 		f.u32(loops.into());
 	}
 	Ok(f.finish()?)
@@ -28,8 +28,8 @@ pub fn read(arc: &GameData, data: &[u8]) -> Result<BTreeMap<ItemId, NameDesc>, R
 		let chunkdata = chunks.get(file).unwrap();
 
 		let mut h = Bytes::new(chunkdata).at((index as usize) * 4)?;
-		let name = h.ptr()?.string()?;
-		let desc = h.ptr()?.string()?;
+		let name = h.ptr()?.string(&arc.encoding)?;
+		let desc = h.ptr()?.string(&arc.encoding)?;
 
 		table.insert(id, NameDesc { name, desc });
 	}
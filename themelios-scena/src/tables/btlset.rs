@@ -4,7 +4,7 @@ use std::rc::Rc;
 use enumflags2::*;
 use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
-use crate::gamedata::Lookup;
+use crate::gamedata::GameData;
 use crate::tables::bgmtbl::BgmId;
 use crate::util::*;
 
@@ -103,6 +103,7 @@ fn read_placement<'a>(
 }
 
 fn read_battlefield<'a>(
+	game: &GameData,
 	f: &mut (impl In<'a> + Clone),
 	battlefields: &mut BTreeMap<u16, Rc<Battlefield>>,
 ) -> Result<Rc<Battlefield>, ReadError> {
@@ -111,20 +112,20 @@ fn read_battlefield<'a>(
 		let unk1 = g.u16()?;
 		let l1 = g.u16()? as usize;
 		ensure!(g.pos() == l1, "invalid battlefield");
-		let battlefield = g.string()?;
+		let battlefield = g.string(&game.encoding)?;
 		Ok(Battlefield(unk1, battlefield))
 	})
 }
 
 fn read_battles<'a>(
-	lookup: &dyn Lookup,
+	game: &GameData,
 	mut f: impl In<'a> + Clone,
 ) -> Result<BTreeMap<BattleId, Battle>, ReadError> {
 	let mut placements = BTreeMap::new();
 	let mut battlefields = BTreeMap::new();
 	let mut at_rolls = BTreeMap::new();
 
-	let fileref = |a| if a == 0 { Ok(None) } else { lookup.name(a).map(Some) };
+	let fileref = |a| if a == 0 { Ok(None) } else { game.lookup.name(a).map(Some) };
 
 	let mut table = BTreeMap::new();
 
@@ -143,7 +144,7 @@ fn read_battles<'a>(
 
 		let placement1 = read_placement(&mut g, &mut placements)?;
 		let placement2 = read_placement(&mut g, &mut placements)?;
-		let battlefield = read_battlefield(&mut g, &mut battlefields)?;
+		let battlefield = read_battlefield(game, &mut g, &mut battlefields)?;
 		g.check_u16(0)?;
 
 		let bgm = BgmId(g.u16()?);
@@ -194,12 +195,12 @@ fn read_battles<'a>(
 }
 
 fn read_auto_battles<'a>(
-	lookup: &dyn Lookup,
+	game: &GameData,
 	mut f: impl In<'a> + Clone,
 ) -> Result<BTreeMap<BattleId, AutoBattle>, ReadError> {
 	let mut battlefields = BTreeMap::new();
 
-	let fileref = |a| if a == 0 { Ok(None) } else { lookup.name(a).map(Some) };
+	let fileref = |a| if a == 0 { Ok(None) } else { game.lookup.name(a).map(Some) };
 
 	let mut table = BTreeMap::new();
 
@@ -209,7 +210,7 @@ fn read_auto_battles<'a>(
 		let id = BattleId(cast(g.u16()?)?);
 
 		let unk1 = g.u16()?;
-		let battlefield = read_battlefield(&mut g, &mut battlefields)?;
+		let battlefield = read_battlefield(game, &mut g, &mut battlefields)?;
 		g.check_u16(0)?;
 
 		let side1: [_; 8] = array(|| Ok(fileref(g.u32()?)?)).strict()?;
@@ -235,11 +236,11 @@ fn read_auto_battles<'a>(
 }
 
 #[allow(clippy::type_complexity)]
-pub fn read(lookup: &dyn Lookup, data: &[u8]) -> Result<(BTreeMap<BattleId, Battle>, BTreeMap<BattleId, AutoBattle>), ReadError> {
+pub fn read(game: &GameData, data: &[u8]) -> Result<(BTreeMap<BattleId, Battle>, BTreeMap<BattleId, AutoBattle>), ReadError> {
 	let mut f = Coverage::new(Bytes::new(data));
 
-	let battles = read_battles(lookup, f.ptr()?)?;
-	let auto_battles = read_auto_battles(lookup, f.ptr()?)?;
+	let battles = read_battles(game, f.ptr()?)?;
+	let auto_battles = read_auto_battles(game, f.ptr()?)?;
 
 	// f.assert_covered()?; // Does not have full coverage
 	Ok((battles, auto_battles))
@@ -4,6 +4,7 @@ use enumflags2::*;
 use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
 use hamu::write::le::*;
+use crate::gamedata::GameData;
 use crate::util::*;
 use super::item::ItemId;
 
@@ -29,7 +30,7 @@ pub struct Recipe {
 	pub heal: u16,
 }
 
-pub fn read(data: &[u8]) -> Result<BTreeMap<RecipeId, Recipe>, ReadError> {
+pub fn read(game: &GameData, data: &[u8]) -> Result<BTreeMap<RecipeId, Recipe>, ReadError> {
 	let mut f = Coverage::new(Bytes::new(data));
 	let n = f.clone().u16()? / 2;
 	let mut table = BTreeMap::new();
@@ -43,7 +44,7 @@ pub fn read(data: &[u8]) -> Result<BTreeMap<RecipeId, Recipe>, ReadError> {
 		let result = ItemId(g.u16()?);
 		g.check_u16(0)?;
 		let heal = g.u16()?;
-		let name_desc = g.name_desc()?;
+		let name_desc = g.name_desc(&game.encoding)?;
 
 		table.insert(id, Recipe { name_desc, ingredients, flags, result, heal });
 	}
@@ -52,7 +53,7 @@ pub fn read(data: &[u8]) -> Result<BTreeMap<RecipeId, Recipe>, ReadError> {
 	Ok(table)
 }
 
-pub fn write(table: &BTreeMap<RecipeId, Recipe>) -> Result<Vec<u8>, WriteError> {
+pub fn write(game: &GameData, table: &BTreeMap<RecipeId, Recipe>) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	let mut g = OutBytes::new();
 
@@ -65,7 +66,7 @@ pub fn write(table: &BTreeMap<RecipeId, Recipe>) -> Result<Vec<u8>, WriteError>
 		g.u16(result.0);
 		g.u16(0);
 		g.u16(heal);
-		g.name_desc(name_desc)?;
+		g.name_desc(&game.encoding, name_desc)?;
 	}
 	Ok(f.concat(g).finish()?)
 }
@@ -4,6 +4,7 @@ use enumflags2::*;
 use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
 use hamu::write::le::*;
+use crate::gamedata::GameData;
 use crate::util::*;
 
 newtype!(ItemId, u16);
@@ -34,7 +35,7 @@ pub struct Item {
 	pub price: u32,
 }
 
-pub fn read(t_item: &[u8], t_item2: &[u8]) -> Result<BTreeMap<ItemId, Item>, ReadError> {
+pub fn read(game: &GameData, t_item: &[u8], t_item2: &[u8]) -> Result<BTreeMap<ItemId, Item>, ReadError> {
 	let mut f1 = Coverage::new(Bytes::new(t_item));
 	let mut f2 = Coverage::new(Bytes::new(t_item2));
 	let n = f1.clone().u16()? / 2;
@@ -60,7 +61,7 @@ pub fn read(t_item: &[u8], t_item2: &[u8]) -> Result<BTreeMap<ItemId, Item>, Rea
 		let limit = g1.u16()?;
 		let price = g1.u32()?;
 
-		let name_desc = g2.name_desc()?;
+		let name_desc = g2.name_desc(&game.encoding)?;
 
 		table.insert(id, Item { name_desc, flags, usable_by, ty, _unk1, stats, limit, price });
 	}
@@ -70,7 +71,7 @@ pub fn read(t_item: &[u8], t_item2: &[u8]) -> Result<BTreeMap<ItemId, Item>, Rea
 	Ok(table)
 }
 
-pub fn write(table: &BTreeMap<ItemId, Item>) -> Result<(Vec<u8>, Vec<u8>), WriteError> {
+pub fn write(game: &GameData, table: &BTreeMap<ItemId, Item>) -> Result<(Vec<u8>, Vec<u8>), WriteError> {
 	let mut f1 = OutBytes::new();
 	let mut g1 = OutBytes::new();
 	let mut f2 = OutBytes::new();
@@ -90,7 +91,7 @@ pub fn write(table: &BTreeMap<ItemId, Item>) -> Result<(Vec<u8>, Vec<u8>), Write
 		g1.u16(limit);
 		g1.u32(price);
 
-		g2.name_desc(name_desc)?;
+		g2.name_desc(&game.encoding, name_desc)?;
 	}
 	Ok((f1.concat(g1).finish()?, f2.concat(g2).finish()?))
 }
@@ -4,6 +4,7 @@ use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
 use hamu::write::le::*;
 use crate::scena::Flag;
+use crate::gamedata::GameData;
 use crate::util::*;
 
 newtype!(QuestId, u16);
@@ -21,7 +22,7 @@ pub struct Quest {
 	pub steps: [String; 16],
 }
 
-pub fn read(data: &[u8]) -> Result<BTreeMap<QuestId, Quest>, ReadError> {
+pub fn read(game: &GameData, data: &[u8]) -> Result<BTreeMap<QuestId, Quest>, ReadError> {
 	let mut f = Coverage::new(Bytes::new(data));
 	let n = f.clone().u16()? / 2;
 	let mut table = BTreeMap::new();
@@ -42,18 +43,18 @@ pub fn read(data: &[u8]) -> Result<BTreeMap<QuestId, Quest>, ReadError> {
 		let descp = g.u16()? as usize;
 		let stepp = array::<16, _>(|| Ok(g.u16()? as usize)).strict()?;
 		ensure!(g.pos() == namep, "{} != {}", g.pos(), namep);
-		let name = g.string()?;
+		let name = g.string(&game.encoding)?;
 		ensure!(g.pos() == descp, "{} != {}", g.pos(), descp);
-		let desc = g.string()?;
+		let desc = g.string(&game.encoding)?;
 
-		let extra_desc = (g.pos() != stepp[0]).then(|| g.string()).transpose()?;
+		let extra_desc = (g.pos() != stepp[0]).then(|| g.string(&game.encoding)).transpose()?;
 
 		let steps = stepp.try_map(|p| {
 			while g.pos() < p {
 				g.check_u8(0)?;
 			}
 			ensure!(g.pos() == p, "{} != {}", g.pos(), p);
-			g.string()
+			g.string(&game.encoding)
 		})?;
 
 		table.insert(id, Quest { section, index, bp, mira, flags, name, desc, extra_desc, steps });
@@ -63,7 +64,7 @@ pub fn read(data: &[u8]) -> Result<BTreeMap<QuestId, Quest>, ReadError> {
 	Ok(table)
 }
 
-pub fn write(table: &BTreeMap<QuestId, Quest>) -> Result<Vec<u8>, WriteError> {
+pub fn write(game: &GameData, table: &BTreeMap<QuestId, Quest>) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	let mut g = OutBytes::new();
 
@@ -83,17 +84,17 @@ pub fn write(table: &BTreeMap<QuestId, Quest>) -> Result<Vec<u8>, WriteError> {
 		let mut h = OutBytes::new();
 
 		g.delay_u16(h.here());
-		h.string(name)?;
+		h.string(&game.encoding, name)?;
 
 		g.delay_u16(h.here());
-		h.string(desc)?;
+		h.string(&game.encoding, desc)?;
 		if let Some(extra_desc) = extra_desc {
-			h.string(extra_desc)?;
+			h.string(&game.encoding, extra_desc)?;
 		}
 
 		for step in steps {
 			g.delay_u16(h.here());
-			h.string(step)?;
+			h.string(&game.encoding, step)?;
 		}
 
 		g = g.concat(h);
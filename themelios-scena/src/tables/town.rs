@@ -1,6 +1,7 @@
 use hamu::read::coverage::Coverage;
 use hamu::read::le::*;
 use hamu::write::le::*;
+use crate::gamedata::GameData;
 use crate::util::*;
 
 newtype!(TownId, u16);
@@ -23,13 +24,13 @@ pub enum TownType {
 	Cafe       = 8, // Cafe             飲食・喫茶
 }
 
-pub fn read(t_town: &[u8]) -> Result<Vec<Town>, ReadError> {
+pub fn read(game: &GameData, t_town: &[u8]) -> Result<Vec<Town>, ReadError> {
 	let mut f = Coverage::new(Bytes::new(t_town));
 	let n = f.u16()?;
 	let mut names = Vec::with_capacity(n as usize);
 	for _ in 0..n {
 		let mut g = f.ptr()?;
-		let name = g.string()?;
+		let name = g.string(&game.encoding)?;
 		let type_ = if name.is_empty() {
 			0
 		} else {
@@ -42,13 +43,13 @@ pub fn read(t_town: &[u8]) -> Result<Vec<Town>, ReadError> {
 	Ok(names)
 }
 
-pub fn write(towns: &[Town]) -> Result<Vec<u8>, WriteError> {
+pub fn write(game: &GameData, towns: &[Town]) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	let mut g = OutBytes::new();
 	f.u16(cast(towns.len())?);
 	for &Town(ref name, kind) in towns {
 		f.delay_u16(g.here());
-		g.string(name)?;
+		g.string(&game.encoding, name)?;
 		if name.is_empty() {
 			ensure!(kind == TownType::None, "empty town must be type None");
 		} else {
@@ -1,8 +1,12 @@
 use hamu::read::le::*;
 use hamu::write::le::*;
+use crate::gamedata::GameData;
+use crate::scena::code::InstructionSet;
 use crate::util::*;
 use crate::tables::item::ItemId;
 
+pub mod reflow;
+
 #[derive(Clone, PartialEq, Eq, derive_more::Deref, derive_more::DerefMut)]
 pub struct Text(#[deref] #[deref_mut] pub Vec<TextSegment>);
 
@@ -25,6 +29,56 @@ pub enum TextSegment {
 	Color(u8),
 	Item(ItemId),
 	Byte(u8), // other byte of unknown meaning
+	/// `#nF`, shows face portrait `n` next to the text.
+	Face(Num),
+	/// `#nP`, sets where the text box and its portrait are placed. This is not a portrait itself;
+	/// those are [`Face`](Self::Face).
+	Position(Num),
+	/// `#nV`, plays voice clip `n`.
+	Voice(Num),
+	/// Any other `#`-prefixed command, such as text size or speed.
+	Command(Num, char),
+}
+
+/// A number in an inline text command, which remembers how many digits it was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Num {
+	pub value: u32,
+	pub width: usize,
+}
+
+impl Num {
+	pub fn new(value: u32) -> Num {
+		Num { value, width: 0 }
+	}
+}
+
+impl std::fmt::Display for Num {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{:0width$}", self.value, width = self.width)
+	}
+}
+
+impl TextSegment {
+	/// Returns the letter and argument of an inline `#` command.
+	pub fn command(&self) -> Option<(char, Num)> {
+		match *self {
+			Self::Face(n) => Some(('F', n)),
+			Self::Position(n) => Some(('P', n)),
+			Self::Voice(n) => Some(('V', n)),
+			Self::Command(n, c) => Some((c, n)),
+			_ => None,
+		}
+	}
+
+	fn from_command(c: char, n: Num) -> TextSegment {
+		match c {
+			'F' => Self::Face(n),
+			'P' => Self::Position(n),
+			'V' => Self::Voice(n),
+			c => Self::Command(n, c),
+		}
+	}
 }
 
 impl std::fmt::Debug for TextSegment {
@@ -38,51 +92,379 @@ impl std::fmt::Debug for TextSegment {
 			Self::Color(v) => f.debug_tuple("Color").field(v).finish(),
 			Self::Item(v) => f.debug_tuple("Item").field(v).finish(),
 			Self::Byte(v) => f.debug_tuple("Byte").field(v).finish(),
+			_ => {
+				let (c, n) = self.command().unwrap();
+				write!(f, "#{n}{c}")
+			}
+		}
+	}
+}
+
+/// Formats the text as markup, in the same style as calmare.
+///
+/// Line breaks are written as is, and `\` followed by a line break is [`TextSegment::Line2`]. Other
+/// control codes are written as `{wait}`, `{page}`, `{color 2}`, `{item 500}` or `{0x05}`, and
+/// inline `#` commands as they appear in the game. `\`, `{` and `}` are escaped with `\`.
+impl std::fmt::Display for Text {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		use std::fmt::Write;
+		for item in self.iter() {
+			match item {
+				TextSegment::String(s) => {
+					for c in s.chars() {
+						if matches!(c, '\\' | '{' | '}') {
+							f.write_char('\\')?;
+						}
+						f.write_char(c)?;
+					}
+				}
+				TextSegment::Line => f.write_str("\n")?,
+				TextSegment::Line2 => f.write_str("\\\n")?,
+				TextSegment::Wait => f.write_str("{wait}")?,
+				TextSegment::Page => f.write_str("{page}")?,
+				TextSegment::Color(n) => write!(f, "{{color {n}}}")?,
+				TextSegment::Item(n) => write!(f, "{{item {}}}", n.0)?,
+				TextSegment::Byte(n) => write!(f, "{{0x{n:02X}}}")?,
+				_ => {
+					let (c, n) = item.command().unwrap();
+					write!(f, "#{n}{c}")?
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{reason} at byte {pos}")]
+pub struct MarkupError { pub pos: usize, pub reason: &'static str }
+
+/// Parses markup as written by the [`Display`](std::fmt::Display) impl.
+impl std::str::FromStr for Text {
+	type Err = MarkupError;
+
+	fn from_str(s: &str) -> Result<Text, MarkupError> {
+		let err = |pos, reason| MarkupError { pos, reason };
+		let mut items = Vec::new();
+		let mut buf = String::new();
+		let mut i = 0;
+		while let Some(c) = s[i..].chars().next() {
+			let start = i;
+			i += c.len_utf8();
+			let item = match c {
+				'\\' => {
+					let Some(c) = s[i..].chars().next() else { return Err(err(start, "unfinished escape")) };
+					i += c.len_utf8();
+					match c {
+						'\n' => TextSegment::Line2,
+						'\\' | '{' | '}' => { buf.push(c); continue }
+						_ => return Err(err(start, "invalid escape")),
+					}
+				}
+				'\n' => TextSegment::Line,
+				'{' => {
+					let Some(len) = s[i..].find('}') else { return Err(err(start, "unclosed {")) };
+					let tag = &s[i..i+len];
+					i += len + 1;
+					parse_tag(tag).ok_or_else(|| err(start, "invalid tag"))?
+				}
+				'}' => return Err(err(start, "unmatched }")),
+				c => { buf.push(c); continue }
+			};
+			split_commands(&buf, &mut items);
+			buf.clear();
+			items.push(item);
 		}
+		split_commands(&buf, &mut items);
+		Ok(Text(items))
 	}
 }
 
+fn parse_tag(tag: &str) -> Option<TextSegment> {
+	Some(match tag.split_once(' ') {
+		None if tag == "wait" => TextSegment::Wait,
+		None if tag == "page" => TextSegment::Page,
+		None => TextSegment::Byte(u8::from_str_radix(tag.strip_prefix("0x")?, 16).ok()?),
+		Some(("color", n)) => TextSegment::Color(n.parse().ok()?),
+		Some(("item", n)) => TextSegment::Item(ItemId(n.parse().ok()?)),
+		_ => return None,
+	})
+}
+
 impl Text {
-	pub fn read<'a>(f: &mut impl In<'a>) -> Result<Text, ReadError> {
+	pub fn read<'a>(f: &mut impl In<'a>, game: &GameData) -> Result<Text, ReadError> {
 		let mut items = Vec::new();
 		loop {
 			items.push(match f.u8()? {
 				0x00 => break,
-				0x01 => TextSegment::Line,
-				0x02 => TextSegment::Wait,
-				0x03 => TextSegment::Page,
-				0x07 => TextSegment::Color(f.u8()?),
-				0x0D => TextSegment::Line2,
-				0x1F => TextSegment::Item(ItemId(f.u16()?)),
-				ch@(0x05 | 0x06 | 0x09 | 0x18) => TextSegment::Byte(ch),
-				ch@(0x0A | 0x0C) => TextSegment::Byte(ch), // Geofront Azure only
-				ch@(0x00..=0x1F) => bail!("b{:?}", char::from(ch)),
+				ch@0x01..=0x1F => match control_code(game, ch) {
+					Some(Code::Line) => TextSegment::Line,
+					Some(Code::Wait) => TextSegment::Wait,
+					Some(Code::Page) => TextSegment::Page,
+					Some(Code::Color) => TextSegment::Color(f.u8()?),
+					Some(Code::Line2) => TextSegment::Line2,
+					Some(Code::Item) => TextSegment::Item(ItemId(f.u16()?)),
+					Some(Code::Unknown) => TextSegment::Byte(ch),
+					None => bail!("invalid text control code 0x{ch:02X} for {:?}", game.iset),
+				},
 				0x20.. => {
 					let start = f.pos() - 1;
 					while f.u8()? >= 0x20 { }
 					let len = f.pos() - start - 1;
 					f.seek(start)?;
-					TextSegment::String(decode(f.slice(len)?)?)
+					let s = game.encoding.decode(f.slice(len)?)?;
+					split_commands(&s, &mut items);
+					continue
 				}
 			})
 		}
 		Ok(Text(items))
 	}
 
-	pub fn write(f: &mut impl Out, v: &Text) -> Result<(), WriteError> {
+	pub fn write(f: &mut impl Out, game: &GameData, v: &Text) -> Result<(), WriteError> {
 		for item in v.iter() {
 			match &item {
-				TextSegment::String(ref s) => f.slice(&encode(s)?),
+				TextSegment::String(ref s) => f.slice(&game.encoding.encode(s)?),
 				TextSegment::Line => f.u8(0x01),
 				TextSegment::Wait => f.u8(0x02),
 				TextSegment::Page => f.u8(0x03),
 				TextSegment::Color(n) => { f.u8(0x07); f.u8(*n); }
 				TextSegment::Line2 => f.u8(0x0D),
 				TextSegment::Item(n) => { f.u8(0x1F); f.u16(n.0); }
-				TextSegment::Byte(n) => f.u8(*n),
+				TextSegment::Byte(n) => {
+					ensure!(control_code(game, *n) == Some(Code::Unknown), "invalid text control code 0x{n:02X} for {:?}", game.iset);
+					f.u8(*n)
+				}
+				_ => {
+					let (c, n) = item.command().unwrap();
+					f.slice(&game.encoding.encode(&format!("#{n}{c}"))?)
+				}
 			}
 		}
 		f.u8(0);
 		Ok(())
 	}
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Code {
+	Line,
+	Wait,
+	Page,
+	Color,
+	Line2,
+	Item,
+	/// Kept as [`TextSegment::Byte`], since what it does is not known.
+	Unknown,
+}
+
+/// The control codes that are valid in each game's text. Anything else is an error.
+pub(crate) fn control_code(game: &GameData, ch: u8) -> Option<Code> {
+	Some(match (game.iset, ch) {
+		(_, 0x01) => Code::Line,
+		(_, 0x02) => Code::Wait,
+		(_, 0x03) => Code::Page,
+		(_, 0x07) => Code::Color,
+		(_, 0x0D) => Code::Line2,
+		(_, 0x1F) => Code::Item,
+		(_, 0x05 | 0x06 | 0x09 | 0x18) => Code::Unknown,
+		// Only seen in Geofront's translation of Azure
+		(InstructionSet::Ao, 0x0A | 0x0C) if game.geofront => Code::Unknown,
+		_ => return None,
+	})
+}
+
+/// Splits a string into plain text and `#` commands, which consist of `#`, a number, and an
+/// uppercase letter. Anything else starting with `#` is left as text.
+fn split_commands(s: &str, items: &mut Vec<TextSegment>) {
+	let mut start = 0;
+	let mut pos = 0;
+	while let Some(i) = s[pos..].find('#') {
+		let hash = pos + i;
+		let digits = s[hash+1..].bytes().take_while(u8::is_ascii_digit).count();
+		let end = hash + 1 + digits;
+		let letter = s[end..].chars().next().filter(char::is_ascii_uppercase);
+		let value = s[hash+1..end].parse().ok();
+		pos = hash + 1;
+		if let Some(c) = letter && let Some(value) = value {
+			if start < hash {
+				items.push(TextSegment::String(s[start..hash].to_owned()));
+			}
+			items.push(TextSegment::from_command(c, Num { value, width: digits }));
+			start = end + 1;
+			pos = start;
+		}
+	}
+	if start < s.len() {
+		items.push(TextSegment::String(s[start..].to_owned()));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn split(s: &str) -> Vec<TextSegment> {
+		let mut items = Vec::new();
+		split_commands(s, &mut items);
+		items
+	}
+
+	fn join(items: &[TextSegment]) -> String {
+		items.iter().map(|item| match item {
+			TextSegment::String(s) => s.clone(),
+			_ => {
+				let (c, n) = item.command().unwrap();
+				format!("#{n}{c}")
+			}
+		}).collect()
+	}
+
+	fn roundtrip(game: &GameData, bytes: &[u8]) -> Text {
+		let text = Text::read(&mut Bytes::new(bytes), game).unwrap();
+		let mut out = OutBytes::new();
+		Text::write(&mut out, game, &text).unwrap();
+		assert_eq!(out.finish().unwrap(), bytes);
+		text
+	}
+
+	#[test]
+	fn control_codes() {
+		let fc = GameData { iset: InstructionSet::Fc, ..GameData::ZERO.clone() };
+		let sc = GameData { iset: InstructionSet::Sc, ..GameData::ZERO.clone() };
+		let known = [0x01, 0x02, 0x03, 0x07, 0x0D, 0x1F];
+		let unknown = [0x05, 0x06, 0x09, 0x18];
+		let geofront = [0x0A, 0x0C];
+		for (game, extra) in [
+			(&fc, &[][..]),
+			(&sc, &[]),
+			(GameData::ZERO, &[]),
+			(GameData::ZERO_EVO, &[]),
+			(GameData::AO, &[]),
+			(GameData::AO_EVO, &[]),
+			(GameData::ZERO_GF, &[]),
+			(GameData::AO_GF, &geofront),
+		] {
+			for ch in 0x01..0x20 {
+				let bytes = match ch {
+					0x07 => vec![ch, 2, 0],
+					0x1F => vec![ch, 0xF4, 0x01, 0],
+					_ => vec![ch, 0],
+				};
+				let is_byte = unknown.contains(&ch) || extra.contains(&ch);
+				if known.contains(&ch) || is_byte {
+					roundtrip(game, &bytes);
+				} else {
+					assert!(Text::read(&mut Bytes::new(&bytes), game).is_err(), "{:?} 0x{ch:02X}", game.iset);
+				}
+				let text = Text(vec![TextSegment::Byte(ch)]);
+				let written = Text::write(&mut OutBytes::new(), game, &text);
+				assert_eq!(written.is_ok(), is_byte, "{:?} 0x{ch:02X}", game.iset);
+			}
+		}
+	}
+
+	#[test]
+	fn markup() {
+		let text = Text(vec![
+			TextSegment::String("a\\b{c}".to_owned()),
+			TextSegment::Line,
+			TextSegment::Face(Num { value: 5, width: 2 }),
+			TextSegment::String("x".to_owned()),
+			TextSegment::Line2,
+			TextSegment::Wait,
+			TextSegment::Page,
+			TextSegment::Color(2),
+			TextSegment::Item(ItemId(500)),
+			TextSegment::Byte(0x05),
+			TextSegment::Voice(Num { value: 123, width: 4 }),
+			TextSegment::Command(Num { value: 3, width: 1 }, 'S'),
+		]);
+		let s = text.to_string();
+		assert_eq!(s, "a\\\\b\\{c\\}\n#05Fx\\\n{wait}{page}{color 2}{item 500}{0x05}#0123V#3S");
+		assert_eq!(s.parse::<Text>().unwrap(), text);
+	}
+
+	#[test]
+	fn markup_escapes() {
+		let text = "\\{0x05\\}\\\\#1F".parse::<Text>().unwrap();
+		assert_eq!(*text, [
+			TextSegment::String("{0x05}\\".to_owned()),
+			TextSegment::Face(Num { value: 1, width: 1 }),
+		]);
+		assert_eq!(text.to_string(), "\\{0x05\\}\\\\#1F");
+	}
+
+	#[test]
+	fn markup_errors() {
+		for (s, pos) in [
+			("a\\", 1),
+			("\\x", 0),
+			("a{wait", 1),
+			("a}", 1),
+			("{color}", 0),
+			("{color x}", 0),
+			("{item 70000}", 0),
+			("{0x}", 0),
+			("{0x100}", 0),
+			("ab{foo 1}", 2),
+		] {
+			assert_eq!(s.parse::<Text>().unwrap_err().pos, pos, "{s:?}");
+		}
+	}
+
+	#[test]
+	fn commands() {
+		let s = "#2F#010P#12345VHello";
+		let items = split(s);
+		assert_eq!(items, [
+			TextSegment::Face(Num { value: 2, width: 1 }),
+			TextSegment::Position(Num { value: 10, width: 3 }),
+			TextSegment::Voice(Num { value: 12345, width: 5 }),
+			TextSegment::String("Hello".to_owned()),
+		]);
+		assert_eq!(join(&items), s);
+
+		let mut bytes = s.as_bytes().to_owned();
+		bytes.push(0);
+		let text = roundtrip(GameData::ZERO, &bytes);
+		assert_eq!(*text, items);
+	}
+
+	#[test]
+	fn other_commands() {
+		let s = "a#3Sb#0000Kc";
+		let items = split(s);
+		assert_eq!(items, [
+			TextSegment::String("a".to_owned()),
+			TextSegment::Command(Num { value: 3, width: 1 }, 'S'),
+			TextSegment::String("b".to_owned()),
+			TextSegment::Command(Num { value: 0, width: 4 }, 'K'),
+			TextSegment::String("c".to_owned()),
+		]);
+		assert_eq!(join(&items), s);
+	}
+
+	#[test]
+	fn not_commands() {
+		for s in ["#", "##", "#F", "#2", "#2f", "#2 F", "# 2F", "#99999999999F"] {
+			assert_eq!(split(s), [TextSegment::String(s.to_owned())], "{s:?}");
+		}
+		let items = split("##1F#");
+		assert_eq!(items, [
+			TextSegment::String("#".to_owned()),
+			TextSegment::Face(Num { value: 1, width: 1 }),
+			TextSegment::String("#".to_owned()),
+		]);
+		assert_eq!(join(&items), "##1F#");
+	}
+
+	#[test]
+	fn num_width() {
+		assert_eq!(Num { value: 7, width: 3 }.to_string(), "007");
+		assert_eq!(Num { value: 1234, width: 2 }.to_string(), "1234");
+		assert_eq!(Num::new(7).to_string(), "7");
+		assert_eq!(split("#007F"), [TextSegment::Face(Num { value: 7, width: 3 })]);
+		assert_ne!(split("#007F"), split("#7F"));
+	}
+}
@@ -0,0 +1,325 @@
+//! Rewrapping text to fit in the text box, for translations where the original line breaks no
+//! longer fit.
+
+use std::collections::HashMap;
+
+use super::{Text, TextSegment};
+
+/// Widths of each glyph, in the same unit as [`Options::width`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Widths {
+	table: HashMap<char, u32>,
+	/// Width of glyphs that are not in the table.
+	pub default: u32,
+}
+
+impl Widths {
+	pub fn new(default: u32) -> Widths {
+		Widths {
+			table: HashMap::new(),
+			default,
+		}
+	}
+
+	/// Widths of the games' own fonts, where ASCII and half-width katakana are `half` wide and
+	/// everything else is twice that.
+	pub fn halfwidth(half: u32) -> Widths {
+		let mut widths = Widths::new(half * 2);
+		for c in (' '..='~').chain('\u{FF61}'..='\u{FF9F}') {
+			widths.set(c, half);
+		}
+		widths
+	}
+
+	pub fn set(&mut self, ch: char, width: u32) {
+		self.table.insert(ch, width);
+	}
+
+	pub fn get(&self, ch: char) -> u32 {
+		self.table.get(&ch).copied().unwrap_or(self.default)
+	}
+
+	/// Parses a width table, with one glyph per line.
+	///
+	/// Each line consists of either a single char or a `U+XXXX` code point, whitespace, and the
+	/// width. Empty lines and lines starting with `#` are ignored, so `#` itself must be written as
+	/// `U+0023`.
+	///
+	/// On error, returns the (zero-based) number of the invalid line.
+	pub fn parse(text: &str, default: u32) -> Result<Widths, usize> {
+		let mut widths = Widths::new(default);
+		for (n, line) in text.lines().enumerate() {
+			if line.trim().is_empty() || line.starts_with('#') {
+				continue
+			}
+			let (ch, width) = line.trim_end().rsplit_once(char::is_whitespace).ok_or(n)?;
+			let ch = ch.trim_start();
+			let ch = if ch.len() > 2 && let Some(hex) = ch.strip_prefix("U+") {
+				u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).ok_or(n)?
+			} else {
+				let mut chars = ch.chars();
+				match (chars.next(), chars.next()) {
+					(Some(ch), None) => ch,
+					_ => return Err(n),
+				}
+			};
+			widths.set(ch, width.parse().map_err(|_| n)?);
+		}
+		Ok(widths)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+	/// Width of the text box.
+	pub width: u32,
+	/// Number of lines that fit in the text box. When a page is full, a [`TextSegment::Wait`] and
+	/// [`TextSegment::Page`] are inserted.
+	pub lines: Option<usize>,
+	/// Width to reserve for [`TextSegment::Item`], since the item's name is not known here.
+	pub item_width: u32,
+}
+
+#[derive(Clone)]
+enum Tok {
+	Char(char),
+	Space,
+	/// An existing line break. Those that are not turned into a [`Tok::Space`] are dropped.
+	Break,
+	Seg(TextSegment),
+}
+
+#[derive(Default)]
+struct Word {
+	space: bool,
+	toks: Vec<Tok>,
+	width: u32,
+	/// Whether another word may start right after this one without a space.
+	closed: bool,
+}
+
+/// Text in these scripts has no spaces, so lines can be broken between any two chars.
+fn breaks_anywhere(c: char) -> bool {
+	c >= '\u{2E80}'
+}
+
+/// Rewraps the text to fit `options.width`.
+///
+/// Existing line breaks are treated as spaces between words, or removed if the text on either side
+/// of them is in a script that is written without spaces. Page breaks are kept. Colors, items and
+/// other control codes stay attached to the word they are next to.
+pub fn reflow(text: &Text, widths: &Widths, options: &Options) -> Text {
+	let mut out = Vec::new();
+	for (i, page) in text.split(|a| *a == TextSegment::Page).enumerate() {
+		if i > 0 {
+			out.push(TextSegment::Page);
+		}
+		reflow_page(page, widths, options, &mut out);
+	}
+	Text(out)
+}
+
+fn reflow_page(page: &[TextSegment], widths: &Widths, options: &Options, out: &mut Vec<TextSegment>) {
+	let tok_width = |tok: &Tok| match tok {
+		Tok::Char(c) => widths.get(*c),
+		Tok::Space => widths.get(' '),
+		Tok::Seg(TextSegment::Item(_)) => options.item_width,
+		Tok::Seg(_) | Tok::Break => 0,
+	};
+
+	let mut toks = Vec::new();
+	for item in page {
+		match item {
+			TextSegment::String(s) => toks.extend(s.chars().map(|c| if c == ' ' { Tok::Space } else { Tok::Char(c) })),
+			TextSegment::Line | TextSegment::Line2 => toks.push(Tok::Break),
+			seg => toks.push(Tok::Seg(seg.clone())),
+		}
+	}
+
+	let tok_char = |tok: &Tok| match tok {
+		Tok::Char(c) => Some(*c),
+		Tok::Space => Some(' '),
+		_ => None,
+	};
+	for i in 0..toks.len() {
+		if let Tok::Break = toks[i] {
+			let prev = toks[..i].iter().rev().find_map(tok_char);
+			let next = toks[i+1..].iter().find_map(tok_char);
+			if !prev.is_some_and(breaks_anywhere) && !next.is_some_and(breaks_anywhere) {
+				toks[i] = Tok::Space;
+			}
+		}
+	}
+
+	let mut words = Vec::new();
+	let mut word = Word::default();
+	for tok in toks {
+		match tok {
+			Tok::Space => {
+				if word.width > 0 || !word.toks.is_empty() {
+					words.push(std::mem::take(&mut word));
+				}
+				word.space = true;
+			}
+			Tok::Char(c) => {
+				if (word.closed || breaks_anywhere(c)) && word.width > 0 {
+					words.push(std::mem::take(&mut word));
+				}
+				word.width += tok_width(&tok);
+				word.closed = breaks_anywhere(c);
+				word.toks.push(tok);
+			}
+			Tok::Seg(_) => {
+				word.width += tok_width(&tok);
+				word.toks.push(tok);
+			}
+			Tok::Break => {}
+		}
+	}
+	if !word.toks.is_empty() {
+		words.push(word);
+	}
+
+	let mut lines = vec![Vec::new()];
+	let mut width = 0;
+	for word in words {
+		let space = if word.space && width > 0 { widths.get(' ') } else { 0 };
+		if width > 0 && width + space + word.width > options.width {
+			lines.push(Vec::new());
+			width = 0;
+		} else if space > 0 {
+			lines.last_mut().unwrap().push(Tok::Space);
+			width += space;
+		}
+		for tok in word.toks {
+			// Only happens for words that are wider than a whole line
+			let w = tok_width(&tok);
+			if width > 0 && w > 0 && width + w > options.width {
+				lines.push(Vec::new());
+				width = 0;
+			}
+			lines.last_mut().unwrap().push(tok);
+			width += w;
+		}
+	}
+
+	for (i, line) in lines.into_iter().enumerate() {
+		if i > 0 {
+			if let Some(n) = options.lines && n > 0 && i % n == 0 {
+				if out.last() != Some(&TextSegment::Wait) {
+					out.push(TextSegment::Wait);
+				}
+				out.push(TextSegment::Page);
+			} else {
+				out.push(TextSegment::Line);
+			}
+		}
+		for tok in line {
+			match tok {
+				Tok::Char(c) => push_char(out, c),
+				Tok::Space => push_char(out, ' '),
+				Tok::Break => {}
+				Tok::Seg(seg) => out.push(seg),
+			}
+		}
+	}
+}
+
+fn push_char(out: &mut Vec<TextSegment>, c: char) {
+	if let Some(TextSegment::String(s)) = out.last_mut() {
+		s.push(c);
+	} else {
+		out.push(TextSegment::String(c.to_string()));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use super::super::ItemId;
+
+	fn s(s: &str) -> TextSegment {
+		TextSegment::String(s.to_owned())
+	}
+
+	fn options(width: u32) -> Options {
+		Options { width, lines: None, item_width: 0 }
+	}
+
+	#[test]
+	fn wrap() {
+		let text = Text(vec![s("the quick brown fox jumps")]);
+		let out = reflow(&text, &Widths::new(1), &options(10));
+		assert_eq!(*out, [s("the quick"), TextSegment::Line, s("brown fox"), TextSegment::Line, s("jumps")]);
+	}
+
+	#[test]
+	fn join_lines() {
+		let text = Text(vec![s("the quick"), TextSegment::Line, s("brown"), TextSegment::Line2, s("fox")]);
+		let out = reflow(&text, &Widths::new(1), &options(40));
+		assert_eq!(*out, [s("the quick brown fox")]);
+	}
+
+	#[test]
+	fn cjk() {
+		let widths = Widths::halfwidth(1);
+		let text = Text(vec![s("あいう"), TextSegment::Line, s("えお")]);
+		assert_eq!(*reflow(&text, &widths, &options(20)), [s("あいうえお")]);
+		assert_eq!(*reflow(&text, &widths, &options(6)), [s("あいう"), TextSegment::Line, s("えお")]);
+
+		// No space is added between latin and CJK text either
+		let text = Text(vec![s("ABC"), TextSegment::Line, s("あ"), TextSegment::Line, s("DEF")]);
+		assert_eq!(*reflow(&text, &widths, &options(20)), [s("ABCあDEF")]);
+		assert_eq!(*reflow(&text, &widths, &options(4)), [s("ABC"), TextSegment::Line, s("あ"), TextSegment::Line, s("DEF")]);
+	}
+
+	#[test]
+	fn too_wide() {
+		let text = Text(vec![s("ab abcdefghijkl")]);
+		let out = reflow(&text, &Widths::new(1), &options(5));
+		assert_eq!(*out, [s("ab"), TextSegment::Line, s("abcde"), TextSegment::Line, s("fghij"), TextSegment::Line, s("kl")]);
+	}
+
+	#[test]
+	fn segments() {
+		let text = Text(vec![s("aa "), TextSegment::Color(2), s("bb "), TextSegment::Item(ItemId(500)), s(" cc")]);
+		let options = Options { width: 7, lines: None, item_width: 4 };
+		let out = reflow(&text, &Widths::new(1), &options);
+		assert_eq!(*out, [
+			s("aa "), TextSegment::Color(2), s("bb"), TextSegment::Line,
+			TextSegment::Item(ItemId(500)), s(" cc"),
+		]);
+	}
+
+	#[test]
+	fn pages() {
+		let text = Text(vec![s("a b c"), TextSegment::Page, s("d")]);
+		let options = Options { width: 1, lines: Some(2), item_width: 0 };
+		let out = reflow(&text, &Widths::new(1), &options);
+		assert_eq!(*out, [
+			s("a"), TextSegment::Line, s("b"), TextSegment::Wait, TextSegment::Page, s("c"),
+			TextSegment::Page, s("d"),
+		]);
+	}
+
+	#[test]
+	fn parse() {
+		let widths = Widths::parse("# comment\n\na 5\nU+0023 3\n  U+3042\t12\nU 7\n", 10).unwrap();
+		assert_eq!(widths.get('a'), 5);
+		assert_eq!(widths.get('#'), 3);
+		assert_eq!(widths.get('あ'), 12);
+		assert_eq!(widths.get('U'), 7);
+		assert_eq!(widths.get('b'), 10);
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert_eq!(Widths::parse("a", 0), Err(0));
+		assert_eq!(Widths::parse("a 1\nab 1", 0), Err(1));
+		assert_eq!(Widths::parse("a x", 0), Err(0));
+		assert_eq!(Widths::parse("a -1", 0), Err(0));
+		assert_eq!(Widths::parse("U+110000 1", 0), Err(0));
+		assert_eq!(Widths::parse("U+XY 1", 0), Err(0));
+		assert_eq!(Widths::parse("\n# x\n 1", 0), Err(2));
+	}
+}
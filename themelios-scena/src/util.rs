@@ -10,6 +10,21 @@ pub use strict_result::Strict;
 
 pub type Backtrace = Box<std::backtrace::Backtrace>;
 
+/// The text encoding used by a game's files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+	/// Shift-JIS, as used by nearly all releases.
+	#[default]
+	Cp932,
+	/// GBK, as used by the Chinese releases.
+	Gbk,
+	/// Passed through as is, for fan translations that use it.
+	Utf8,
+	/// Shift-JIS with some glyphs replaced, for fan translations that draw their own letters over
+	/// unused codepoints.
+	Cp932Remap(std::sync::Arc<cp932::Remap>),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("cannot convert {value} into {type_}\n{source}")]
 pub struct CastError {
@@ -1,7 +1,7 @@
 use hamu::read::le::*;
 use std::ops::*;
 
-use super::{Backtrace, ensure};
+use super::{Backtrace, Encoding, ensure};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ReadError {
@@ -46,11 +46,28 @@ impl std::convert::From<std::convert::Infallible> for ReadError {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Invalid SJIS string {text:?}")]
-pub struct DecodeError { text: String }
+#[error("Invalid {encoding:?} string {text:?}")]
+pub struct DecodeError { encoding: Encoding, text: String }
+
+impl Encoding {
+	pub fn decode(&self, bytes: &[u8]) -> Result<String, DecodeError> {
+		let err = |text| DecodeError { encoding: self.clone(), text };
+		match self {
+			Encoding::Cp932 => cp932::decode(bytes).map_err(|_| err(cp932::decode_lossy(bytes))),
+			Encoding::Gbk => encoding_rs::GBK.decode_without_bom_handling_and_without_replacement(bytes)
+				.map(|a| a.into_owned())
+				.ok_or_else(|| err(encoding_rs::GBK.decode_without_bom_handling(bytes).0.into_owned())),
+			Encoding::Utf8 => String::from_utf8(bytes.to_owned())
+				.map_err(|_| err(String::from_utf8_lossy(bytes).into_owned())),
+			Encoding::Cp932Remap(remap) => cp932::decode_with(bytes, remap)
+				.map_err(|_| err(cp932::decode_lossy_with(bytes, remap))),
+		}
+	}
+}
 
+/// Decodes SJIS, for things that do not depend on the game, such as file names.
 pub fn decode(bytes: &[u8]) -> Result<String, DecodeError> {
-	cp932::decode(bytes).map_err(|_| DecodeError { text: cp932::decode_lossy(bytes) })
+	Encoding::Cp932.decode(bytes)
 }
 
 pub trait InExt1<'a>: In<'a> {
@@ -62,7 +79,7 @@ pub trait InExt1<'a>: In<'a> {
 		Ok(self.clone().at(self.u32()? as usize)?)
 	}
 
-	fn string(&mut self) -> Result<String, ReadError> {
+	fn string(&mut self, enc: &Encoding) -> Result<String, ReadError> {
 		let mut buf = Vec::new();
 		loop {
 			match self.array()? {
@@ -70,7 +87,7 @@ pub trait InExt1<'a>: In<'a> {
 				[n] => buf.push(n),
 			}
 		}
-		Ok(decode(&buf)?)
+		Ok(enc.decode(&buf)?)
 	}
 
 	fn multiple<const N: usize, A: PartialEq + std::fmt::Debug>(
@@ -117,18 +134,18 @@ pub trait InExt1<'a>: In<'a> {
 		})
 	}
 
-	fn sized_string<const N: usize>(&mut self) -> Result<String, ReadError> {
+	fn sized_string<const N: usize>(&mut self, enc: &Encoding) -> Result<String, ReadError> {
 		let buf = self.multiple::<N, _>(&[0], |a| Ok(a.u8()?))?;
-		Ok(decode(&buf)?)
+		Ok(enc.decode(&buf)?)
 	}
 
-	fn name_desc(&mut self) -> Result<super::NameDesc, ReadError> {
+	fn name_desc(&mut self, enc: &Encoding) -> Result<super::NameDesc, ReadError> {
 		let l1 = self.u16()? as usize;
 		let l2 = self.u16()? as usize;
 		ensure!(self.pos() == l1, "invalid NameDesc");
-		let name = self.string()?;
+		let name = self.string(enc)?;
 		ensure!(self.pos() == l2, "invalid NameDesc");
-		let desc = self.string()?;
+		let desc = self.string(enc)?;
 		Ok(super::NameDesc { name, desc })
 	}
 }
@@ -1,7 +1,7 @@
 use hamu::write::le::*;
 use std::ops::*;
 
-use super::{Backtrace, ensure};
+use super::{Backtrace, Encoding, ensure};
 
 #[derive(Debug, thiserror::Error)]
 pub enum WriteError {
@@ -43,16 +43,32 @@ impl std::convert::From<std::convert::Infallible> for WriteError {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Cannot encode {text:?} as SJIS")]
-pub struct EncodeError { text: String }
+#[error("Cannot encode {text:?} as {encoding:?}")]
+pub struct EncodeError { encoding: Encoding, text: String }
+
+impl Encoding {
+	pub fn encode(&self, text: &str) -> Result<Vec<u8>, EncodeError> {
+		let err = || EncodeError { encoding: self.clone(), text: text.to_owned() };
+		match self {
+			Encoding::Cp932 => cp932::encode(text).map_err(|_| err()),
+			Encoding::Gbk => match encoding_rs::GBK.encode(text) {
+				(_, _, true) => Err(err()),
+				(bytes, _, false) => Ok(bytes.into_owned()),
+			},
+			Encoding::Utf8 => Ok(text.as_bytes().to_owned()),
+			Encoding::Cp932Remap(remap) => cp932::encode_with(text, remap).map_err(|_| err()),
+		}
+	}
+}
 
+/// Encodes SJIS, for things that do not depend on the game, such as file names.
 pub fn encode(text: &str) -> Result<Vec<u8>, EncodeError> {
-	cp932::encode(text).map_err(|_| EncodeError { text: text.to_owned() })
+	Encoding::Cp932.encode(text)
 }
 
 pub trait OutExt1: Out {
-	fn string(&mut self, s: &str) -> Result<(), WriteError> {
-		let s = encode(s)?;
+	fn string(&mut self, enc: &Encoding, s: &str) -> Result<(), WriteError> {
+		let s = enc.encode(s)?;
 		self.slice(&s);
 		self.array([0]);
 		Ok(())
@@ -90,8 +106,8 @@ pub trait OutExt1: Out {
 		Ok(())
 	}
 
-	fn sized_string<const N: usize>(&mut self, s: &str) -> Result<(), WriteError> {
-		let s = encode(s)?;
+	fn sized_string<const N: usize>(&mut self, enc: &Encoding, s: &str) -> Result<(), WriteError> {
+		let s = enc.encode(s)?;
 		// Not using multiple() here to include the string in the error
 		ensure!(s.len() <= N, super::cast_error::<[u8; N]>(format!("{s:?}"), "too large").into());
 		let mut buf = [0; N];
@@ -100,16 +116,16 @@ pub trait OutExt1: Out {
 		Ok(())
 	}
 
-	fn name_desc(&mut self, nd: &super::NameDesc) -> Result<(), WriteError> where Self: OutDelay {
+	fn name_desc(&mut self, enc: &Encoding, nd: &super::NameDesc) -> Result<(), WriteError> where Self: OutDelay {
 		let super::NameDesc { name, desc } = nd;
 		let (l1, l1_) = Label::new();
 		let (l2, l2_) = Label::new();
 		self.delay_u16(l1);
 		self.delay_u16(l2);
 		self.label(l1_);
-		self.string(name)?;
+		self.string(enc, name)?;
 		self.label(l2_);
-		self.string(desc)?;
+		self.string(enc, desc)?;
 		Ok(())
 	}
 }
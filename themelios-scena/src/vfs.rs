@@ -0,0 +1,464 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashSet},
+	path::{Path, PathBuf},
+	fs,
+	io,
+	sync::Arc,
+};
+
+use crate::archive::Archives;
+use crate::decompress;
+use crate::gamedata::{Lookup, LookupError, SkyGameData};
+
+type Backtrace = Box<std::backtrace::Backtrace>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("{source}")]
+	Io { #[from] source: io::Error, backtrace: Backtrace },
+
+	#[error(transparent)]
+	Archive { #[from] #[backtrace] source: crate::archive::Error },
+}
+
+/// Something files can be read from, such as [`Archives`] or a [`Dir`] of loose files.
+pub trait Source: Send + Sync {
+	/// Returns the file's raw data, as it is stored, or `None` if there is no such file.
+	fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error>;
+
+	/// Returns the file's data, decompressed if the source stores it compressed.
+	///
+	/// Since files carry no marker of whether they are compressed, a file is decompressed if it is
+	/// a chunk stream according to [`decompress::is_compressed`]. If it is, but fails to
+	/// decompress, that is an error rather than falling back to the raw data.
+	fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error>;
+
+	fn list(&self) -> Box<dyn Iterator<Item=&str> + '_>;
+
+	/// The file reference lookup for this source, if it has a notion of file indices.
+	fn lookup(&self) -> Option<&dyn Lookup> {
+		None
+	}
+}
+
+impl Source for Archives {
+	fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error> {
+		Ok(Archives::get(self, name)?.map(Cow::Borrowed))
+	}
+
+	fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		match Archives::get_decomp(self, name)? {
+			Some(data) => Ok(Some(data)),
+			None => Ok(Archives::get(self, name)?.map(<[u8]>::to_vec)),
+		}
+	}
+
+	fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
+		Archives::list(self)
+	}
+
+	fn lookup(&self) -> Option<&dyn Lookup> {
+		Some(self)
+	}
+}
+
+impl<T: Source + ?Sized> Source for Arc<T> {
+	fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error> {
+		T::get(self, name)
+	}
+
+	fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		T::get_decomp(self, name)
+	}
+
+	fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
+		T::list(self)
+	}
+
+	fn lookup(&self) -> Option<&dyn Lookup> {
+		T::lookup(self)
+	}
+}
+
+/// A directory of loose files.
+///
+/// Files are named by their lowercased path relative to the directory, using `/` as separator, so a
+/// flat directory uses the same names as the archives do. Loose files are usually stored
+/// uncompressed, like in the games' own data folders, but compressed ones are decompressed by
+/// [`get_decomp`](Self::get_decomp) too.
+///
+/// This is also how the ED7 games and the Evo releases store their data: a `Dir` on their `data`
+/// folder, given an [`ED7Lookup`](crate::gamedata::ED7Lookup) with [`with_lookup`](Self::with_lookup),
+/// can be used the same way as [`Archives`]. For override directories on top of the Sky games'
+/// archives, see [`Vfs::sky`].
+#[derive(Clone)]
+pub struct Dir {
+	path: PathBuf,
+	files: BTreeMap<String, PathBuf>,
+	lookup: Option<Arc<dyn Lookup>>,
+}
+
+impl std::fmt::Debug for Dir {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Dir")
+			.field("path", &self.path)
+			.field("files", &self.files.len())
+			.field("lookup", &self.lookup.is_some())
+			.finish()
+	}
+}
+
+impl Dir {
+	pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+		fn walk(dir: &Path, prefix: &str, files: &mut BTreeMap<String, PathBuf>) -> io::Result<()> {
+			for ent in fs::read_dir(dir)? {
+				let ent = ent?;
+				let Some(name) = ent.file_name().to_str().map(str::to_lowercase) else { continue };
+				let name = format!("{prefix}{name}");
+				if ent.file_type()?.is_dir() {
+					walk(&ent.path(), &format!("{name}/"), files)?;
+				} else {
+					files.insert(name, ent.path());
+				}
+			}
+			Ok(())
+		}
+
+		let mut files = BTreeMap::new();
+		walk(path.as_ref(), "", &mut files)?;
+		Ok(Dir {
+			path: path.as_ref().to_owned(),
+			files,
+			lookup: None,
+		})
+	}
+
+	/// Sets how file references are mapped to the names in this directory.
+	///
+	/// Only files that actually exist in the directory are resolved.
+	pub fn with_lookup(mut self, lookup: impl Lookup + 'static) -> Self {
+		self.lookup = Some(Arc::new(lookup));
+		self
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.files.contains_key(name)
+	}
+
+	/// Returns the path of the file with the given name, if it exists.
+	pub fn file(&self, name: &str) -> Option<&Path> {
+		self.files.get(name).map(PathBuf::as_path)
+	}
+
+	/// Reads the file with the given name, or returns `None` if there is no such file. A file that
+	/// was deleted after the directory was listed counts as not existing, but any other error in
+	/// reading it is returned.
+	pub fn get(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+		let Some(path) = self.file(name) else { return Ok(None) };
+		match fs::read(path) {
+			Ok(data) => Ok(Some(data)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Like [`get`](Self::get), but decompresses the file if it is compressed. Uncompressed files
+	/// are returned as is.
+	pub fn get_decomp(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+		match self.get(name)? {
+			Some(data) if decompress::is_compressed(&data) => Ok(Some(decompress::decompress(&data)?)),
+			data => Ok(data),
+		}
+	}
+
+	pub fn list(&self) -> impl Iterator<Item=&str> {
+		self.files.keys().map(String::as_str)
+	}
+
+	pub fn name(&self, index: u32) -> Option<String> {
+		let name = self.lookup.as_ref()?.name(index).ok()?;
+		self.contains(&name).then_some(name)
+	}
+
+	pub fn index(&self, name: &str) -> Option<u32> {
+		if !self.contains(name) {
+			return None
+		}
+		self.lookup.as_ref()?.index(name).ok()
+	}
+}
+
+impl Lookup for Dir {
+	fn name(&self, index: u32) -> Result<String, LookupError> {
+		Dir::name(self, index).ok_or_else(|| index.into())
+	}
+
+	fn index(&self, name: &str) -> Result<u32, LookupError> {
+		Dir::index(self, name).ok_or_else(|| name.into())
+	}
+}
+
+impl Source for Dir {
+	fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error> {
+		Ok(Dir::get(self, name)?.map(Cow::Owned))
+	}
+
+	fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		Ok(Dir::get_decomp(self, name)?)
+	}
+
+	fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
+		Box::new(Dir::list(self))
+	}
+
+	fn lookup(&self) -> Option<&dyn Lookup> {
+		self.lookup.is_some().then_some(self as &dyn Lookup)
+	}
+}
+
+/// Maps indices within one of the archives to names, as needed by [`SkyGameData`].
+struct InArchive {
+	archives: Arc<Archives>,
+	num: u16,
+}
+
+impl Lookup for InArchive {
+	fn name(&self, index: u32) -> Result<String, LookupError> {
+		self.archives.archive(self.num)?
+			.and_then(|a| a.name(index as usize))
+			.map(str::to_owned)
+			.ok_or_else(|| index.into())
+	}
+
+	fn index(&self, name: &str) -> Result<u32, LookupError> {
+		self.archives.archive(self.num)?
+			.and_then(|a| a.index(name))
+			.map(|a| a as u32)
+			.ok_or_else(|| name.into())
+	}
+}
+
+/// A stack of [`Source`]s, where files in later layers take priority over earlier ones.
+///
+/// Typically this is the [`Archives`] at the bottom with one or more override [`Dir`]s on top,
+/// which gives the same view of the files as the game has.
+#[derive(Default)]
+pub struct Vfs {
+	layers: Vec<Box<dyn Source>>,
+}
+
+impl Vfs {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The files of a Sky game on PC: the archives in `path`, overridden by loose files in
+	/// `overrides`.
+	///
+	/// `overrides` has a subdirectory for each archive it overrides, named like the archive
+	/// (`ED6_DT01` and so on). Loose files there resolve to the same file references as the
+	/// archive entries they replace, through [`SkyGameData`]. If `overrides` does not exist, as is
+	/// the case without any mods installed, only the archives are used.
+	pub fn sky(path: impl AsRef<Path>, overrides: impl AsRef<Path>) -> Result<Self, Error> {
+		let archives = Arc::new(Archives::new(path)?);
+		let mut dirs = BTreeMap::new();
+		let entries = match fs::read_dir(overrides) {
+			Ok(entries) => entries,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vfs::new().with(archives)),
+			Err(e) => return Err(e.into()),
+		};
+		for ent in entries {
+			let ent = ent?;
+			let Some(num) = ent.file_name().to_str()
+				.and_then(|a| a.strip_prefix("ED6_DT"))
+				.filter(|a| a.len() == 2)
+				.and_then(|a| u8::from_str_radix(a, 16).ok())
+			else { continue };
+			if !ent.file_type()?.is_dir() {
+				continue
+			}
+			let num = num as u16;
+			// References to archive 1B are written as 1A, see Archives::index
+			let id = if num == 0x1B { 0x1A } else { num };
+			let lookup = SkyGameData(id, InArchive { archives: archives.clone(), num });
+			dirs.insert(num, Dir::new(ent.path())?.with_lookup(lookup));
+		}
+
+		let mut vfs = Vfs::new().with(archives);
+		for (_, dir) in dirs {
+			vfs.push(dir);
+		}
+		Ok(vfs)
+	}
+
+	/// Adds a layer on top of the existing ones.
+	pub fn push(&mut self, source: impl Source + 'static) {
+		self.layers.push(Box::new(source));
+	}
+
+	pub fn with(mut self, source: impl Source + 'static) -> Self {
+		self.push(source);
+		self
+	}
+
+	/// The layers, from lowest to highest priority.
+	pub fn layers(&self) -> &[Box<dyn Source>] {
+		&self.layers
+	}
+
+	/// Returns the file from the highest layer that has it. Errors are returned immediately, rather
+	/// than falling back to lower layers.
+	pub fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error> {
+		for layer in self.layers.iter().rev() {
+			if let Some(data) = layer.get(name)? {
+				return Ok(Some(data))
+			}
+		}
+		Ok(None)
+	}
+
+	pub fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		for layer in self.layers.iter().rev() {
+			if let Some(data) = layer.get_decomp(name)? {
+				return Ok(Some(data))
+			}
+		}
+		Ok(None)
+	}
+
+	/// Lists all visible files, in priority order. Files that are overridden are only listed once.
+	pub fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
+		let mut seen = HashSet::new();
+		Box::new(
+			self.layers.iter().rev()
+			.flat_map(|a| a.list())
+			.filter(move |a| seen.insert(*a))
+		)
+	}
+}
+
+impl Source for Vfs {
+	fn get(&self, name: &str) -> Result<Option<Cow<[u8]>>, Error> {
+		Vfs::get(self, name)
+	}
+
+	fn get_decomp(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+		Vfs::get_decomp(self, name)
+	}
+
+	fn list(&self) -> Box<dyn Iterator<Item=&str> + '_> {
+		Vfs::list(self)
+	}
+
+	fn lookup(&self) -> Option<&dyn Lookup> {
+		Some(self)
+	}
+}
+
+impl Lookup for Vfs {
+	fn name(&self, index: u32) -> Result<String, LookupError> {
+		self.layers.iter().rev()
+			.filter_map(|a| a.lookup())
+			.find_map(|a| a.name(index).ok())
+			.ok_or_else(|| index.into())
+	}
+
+	fn index(&self, name: &str) -> Result<u32, LookupError> {
+		self.layers.iter().rev()
+			.filter_map(|a| a.lookup())
+			.find_map(|a| a.index(name).ok())
+			.ok_or_else(|| name.into())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn dir_errors() -> Result<(), Error> {
+		let tmp = tempfile::tempdir()?;
+		fs::write(tmp.path().join("a._dt"), b"a")?;
+		fs::write(tmp.path().join("b._dt"), b"b")?;
+		let dir = Dir::new(tmp.path())?;
+		assert_eq!(dir.get("a._dt")?.as_deref(), Some(&b"a"[..]));
+		assert_eq!(dir.get("c._dt")?, None);
+
+		fs::remove_file(tmp.path().join("a._dt"))?;
+		assert_eq!(dir.get("a._dt")?, None);
+
+		fs::remove_file(tmp.path().join("b._dt"))?;
+		fs::create_dir(tmp.path().join("b._dt"))?;
+		assert!(dir.get("b._dt").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn decomp() -> Result<(), Error> {
+		use crate::archive::{ArchiveBuilder, BuildEntry};
+		use crate::compress::{self, Mode};
+		let compressed = compress::compress(b"compressed", Mode::Bits, compress::DEFAULT_LEVEL);
+		// A single chunk with a verbatim run of 31 bytes, but only one byte following it
+		let corrupt = [4, 0, 0x1F, b'a', 0];
+		let tmp = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("raw._dt", b"raw".as_slice(), 1));
+		b.push(BuildEntry::new("comp._dt", compressed.clone(), 1));
+		b.push(BuildEntry::new("corrupt._dt", corrupt.as_slice(), 1));
+		b.save(tmp.path(), 0)?;
+		let loose = tmp.path().join("loose");
+		fs::create_dir(&loose)?;
+		fs::write(loose.join("loose._dt"), &compressed)?;
+		fs::write(loose.join("corrupt._dt"), &corrupt)?;
+
+		let vfs = Vfs::new().with(Archives::new(tmp.path())?);
+		assert_eq!(vfs.get_decomp("raw._dt")?.as_deref(), Some(&b"raw"[..]));
+		assert_eq!(vfs.get_decomp("comp._dt")?.as_deref(), Some(&b"compressed"[..]));
+		assert!(vfs.get_decomp("corrupt._dt").is_err());
+
+		let vfs = vfs.with(Dir::new(&loose)?);
+		assert_eq!(vfs.get_decomp("loose._dt")?.as_deref(), Some(&b"compressed"[..]));
+		assert!(vfs.get_decomp("corrupt._dt").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn sky_overrides() -> Result<(), Error> {
+		use crate::archive::{ArchiveBuilder, BuildEntry};
+		let tmp = tempfile::tempdir()?;
+		let mut b = ArchiveBuilder::new();
+		b.push(BuildEntry::new("t0100._sn", b"archived".as_slice(), 0));
+		b.push(BuildEntry::new("t0200._sn", b"archived 2".as_slice(), 0));
+		b.save(tmp.path(), 0x01)?;
+		let overrides = tmp.path().join("data");
+		fs::create_dir_all(overrides.join("ED6_DT01"))?;
+		fs::write(overrides.join("ED6_DT01/t0100._sn"), b"loose")?;
+		fs::write(overrides.join("ED6_DT01/extra._dt"), b"extra")?;
+
+		let vfs = Vfs::sky(tmp.path(), &overrides)?;
+		assert_eq!(vfs.layers().len(), 2);
+		assert_eq!(vfs.get("t0100._sn")?.as_deref(), Some(&b"loose"[..]));
+		assert_eq!(vfs.get("t0200._sn")?.as_deref(), Some(&b"archived 2"[..]));
+		assert_eq!(vfs.get("extra._dt")?.as_deref(), Some(&b"extra"[..]));
+		assert_eq!(vfs.get("t0300._sn")?, None);
+
+		let mut list = vfs.list().collect::<Vec<_>>();
+		list.sort();
+		assert_eq!(list, ["extra._dt", "t0100._sn", "t0200._sn"]);
+
+		assert_eq!(vfs.index("t0100._sn").ok(), Some(0x0001_0000));
+		assert_eq!(vfs.name(0x0001_0000).ok().as_deref(), Some("t0100._sn"));
+		assert_eq!(vfs.index("t0200._sn").ok(), Some(0x0001_0001));
+		assert!(vfs.index("extra._dt").is_err());
+
+		let vfs = Vfs::sky(tmp.path(), tmp.path().join("missing"))?;
+		assert_eq!(vfs.layers().len(), 1);
+		assert_eq!(vfs.get("t0100._sn")?.as_deref(), Some(&b"archived"[..]));
+		Ok(())
+	}
+}
@@ -21,7 +21,7 @@ pub fn read_ed7(game: &GameData, data: &[u8]) -> Result<Vec<ED7Name>, ReadError>
 	let fileref = |a| if a == 0 { Ok(None) } else { game.lookup.name(a).map(Some) };
 	loop {
 		let id = NameId(f.u16()?);
-		let name = f.ptr()?.string()?;
+		let name = f.ptr()?.string(&game.encoding)?;
 		let chcp1 = fileref(f.u32()?)?;
 		let chcp2 = fileref(f.u32()?)?;
 		let ms1 = fileref(f.u32()?)?;
@@ -43,7 +43,7 @@ pub fn write_ed7(game: &GameData, table: &[ED7Name]) -> Result<Vec<u8>, WriteErr
 		f.u32(fileref(name.chcp2.as_deref())?);
 		f.u32(fileref(name.ms1.as_deref())?);
 		f.u32(fileref(name.ms2.as_deref())?);
-		g.string(&name.name)?;
+		g.string(&game.encoding, &name.name)?;
 
 	}
 	f.u16(999);
@@ -52,7 +52,7 @@ pub fn write_ed7(game: &GameData, table: &[ED7Name]) -> Result<Vec<u8>, WriteErr
 	f.u32(0);
 	f.u32(0);
 	f.u32(0);
-	g.string(" ")?;
+	g.string(&game.encoding, " ")?;
 
 	f.append(g);
 	Ok(f.finish()?)
@@ -21,7 +21,7 @@ pub struct ED6Quest {
 	pub steps: Vec<Text>,
 }
 
-pub fn read_ed6(_game: &GameData, data: &[u8]) -> Result<Vec<ED6Quest>, ReadError> {
+pub fn read_ed6(game: &GameData, data: &[u8]) -> Result<Vec<ED6Quest>, ReadError> {
 	let mut f = Bytes::new(data);
 	let n = f.clone().u16()? / 2;
 	let mut table = Vec::new();
@@ -38,11 +38,11 @@ pub fn read_ed6(_game: &GameData, data: &[u8]) -> Result<Vec<ED6Quest>, ReadErro
 		let mira = g.u16()?;
 		let flags = array(|| Ok(Flag(g.u16()?))).strict()?;
 
-		let name = g.ptr()?.string()?;
-		let desc = Text::read(&mut g.ptr()?)?;
+		let name = g.ptr()?.string(&game.encoding)?;
+		let desc = Text::read(&mut g.ptr()?, game)?;
 		let mut steps = Vec::new();
 		for _ in 0..16 {
-			steps.push(Text::read(&mut g.ptr()?)?);
+			steps.push(Text::read(&mut g.ptr()?, game)?);
 		}
 
 		table.push(ED6Quest { id, section, index, bp, mira, flags, name, desc, steps });
@@ -51,7 +51,7 @@ pub fn read_ed6(_game: &GameData, data: &[u8]) -> Result<Vec<ED6Quest>, ReadErro
 	Ok(table)
 }
 
-pub fn write_ed6(_game: &GameData, table: &[ED6Quest]) -> Result<Vec<u8>, WriteError> {
+pub fn write_ed6(game: &GameData, table: &[ED6Quest]) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	let mut g = OutBytes::new();
 
@@ -71,12 +71,12 @@ pub fn write_ed6(_game: &GameData, table: &[ED6Quest]) -> Result<Vec<u8>, WriteE
 		let mut h = OutBytes::new();
 
 		g.delay_u16(h.here());
-		h.string(name)?;
+		h.string(&game.encoding, name)?;
 		g.delay_u16(h.here());
-		Text::write(&mut h, desc)?;
+		Text::write(&mut h, game, desc)?;
 		for step in steps {
 			g.delay_u16(h.here());
-			Text::write(&mut h, step)?;
+			Text::write(&mut h, game, step)?;
 		}
 
 		g.append(h);
@@ -100,7 +100,7 @@ pub struct ED7Quest {
 	pub steps: Vec<Text>,
 }
 
-pub fn read_ed7(_game: &GameData, data: &[u8]) -> Result<Vec<ED7Quest>, ReadError> {
+pub fn read_ed7(game: &GameData, data: &[u8]) -> Result<Vec<ED7Quest>, ReadError> {
 	let mut f = Coverage::new(Bytes::new(data));
 	let mut table = Vec::new();
 	let mut step_ptrs = Vec::new();
@@ -121,9 +121,9 @@ pub fn read_ed7(_game: &GameData, data: &[u8]) -> Result<Vec<ED7Quest>, ReadErro
 		let unk1 = f.u8()?;
 		f.check_u16(0)?;
 		let flags = [Flag(f.u16()?), Flag(f.u16()?)];
-		let name = ptr(f.u32()?)?.string()?;
-		let client = ptr(f.u32()?)?.string()?;
-		let desc = Text::read(&mut ptr(f.u32()?)?)?;
+		let name = ptr(f.u32()?)?.string(&game.encoding)?;
+		let client = ptr(f.u32()?)?.string(&game.encoding)?;
+		let desc = Text::read(&mut ptr(f.u32()?)?, game)?;
 		step_ptrs.push(ptr(f.u32()?)?);
 		table.push(ED7Quest {
 			id,
@@ -144,13 +144,13 @@ pub fn read_ed7(_game: &GameData, data: &[u8]) -> Result<Vec<ED7Quest>, ReadErro
 	for (q, mut g) in table.iter_mut().zip(step_ptrs) {
 		let end = *all_ptrs.range(g.pos()+1..).next().unwrap();
 		while g.pos() + 4 <= end {
-			q.steps.push(Text::read(&mut g.ptr32()?)?);
+			q.steps.push(Text::read(&mut g.ptr32()?, game)?);
 		}
 	}
 	Ok(table)
 }
 
-pub fn write_ed7(_game: &GameData, table: &[ED7Quest]) -> Result<Vec<u8>, WriteError> {
+pub fn write_ed7(game: &GameData, table: &[ED7Quest]) -> Result<Vec<u8>, WriteError> {
 	let mut f = OutBytes::new();
 	let mut g = OutBytes::new();
 	let mut h = OutBytes::new();
@@ -163,13 +163,13 @@ pub fn write_ed7(_game: &GameData, table: &[ED7Quest]) -> Result<Vec<u8>, WriteE
 		f.u16(0);
 		f.u16(q.flags[0].0);
 		f.u16(q.flags[1].0);
-		f.delay_u32(g.here()); g.string(&q.name)?;
-		f.delay_u32(g.here()); g.string(&q.client)?;
-		f.delay_u32(g.here()); Text::write(&mut g, &q.desc)?;
+		f.delay_u32(g.here()); g.string(&game.encoding, &q.name)?;
+		f.delay_u32(g.here()); g.string(&game.encoding, &q.client)?;
+		f.delay_u32(g.here()); Text::write(&mut g, game, &q.desc)?;
 		f.delay_u32(h.here());
 		for task in &q.steps {
 			h.delay_u32(g.here());
-			Text::write(&mut g, task)?;
+			Text::write(&mut g, game, task)?;
 		}
 	}
 	f.append(g);
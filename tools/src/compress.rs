@@ -0,0 +1,32 @@
+use std::{io::{self, Read, Write}, path::{PathBuf, Path}};
+use themelios::compress::{self, Mode};
+
+/// Compress a file or stdin into stdout
+#[derive(Debug, Clone, clap::Args)]
+pub struct Command {
+	/// Use the byte-based format used by ED7, rather than the bit-based one.
+	#[clap(short='2', long)]
+	mode2: bool,
+
+	/// Compression level, from 0 to 10.
+	#[clap(short, long, default_value_t=compress::DEFAULT_LEVEL)]
+	level: u8,
+
+	#[clap(value_hint=clap::ValueHint::FilePath)]
+	path: Option<PathBuf>,
+}
+
+pub fn run(Command {mode2, level, path}: Command) -> Result<(), io::Error> {
+	let mode = if mode2 { Mode::Bytes } else { Mode::Bits };
+	let path = path.as_deref().unwrap_or_else(|| Path::new("-"));
+	let mut input: Box<dyn io::Read> = if path == Path::new("-") {
+		Box::new(io::stdin().lock())
+	} else {
+		Box::new(std::fs::File::open(path)?)
+	};
+
+	let mut data = Vec::new();
+	input.read_to_end(&mut data)?;
+	io::stdout().lock().write_all(&compress::compress(&data, mode, level))?;
+	Ok(())
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+use themelios::{decompress, scena::detect};
+
+/// Guess which game a scena file is from
+#[derive(Debug, Clone, clap::Args)]
+pub struct Command {
+	/// Decompress the file first, as needed for `._sn` files from the archives
+	#[clap(short, long)]
+	decompress: bool,
+	/// Show all candidates, not only the best ones
+	#[clap(short, long)]
+	all: bool,
+	#[clap(value_hint=clap::ValueHint::FilePath)]
+	path: PathBuf,
+}
+
+pub fn run(Command { decompress, all, path }: Command) -> Result<(), std::io::Error> {
+	let mut data = std::fs::read(&path)?;
+	if decompress {
+		data = decompress::decompress(&data)?;
+	}
+
+	let candidates = detect::detect(&data);
+	let best = candidates.first().map_or(0., |a| a.evidence.score());
+	for c in &candidates {
+		if all || c.evidence.score() == best {
+			println!("{:.2} {c}", c.evidence.score());
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use themelios::archive::{Archives, diff::Diff};
+use eyre::*;
+
+/// Compare the archives of two game installs.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Command {
+	/// Directory containing the first set of .dir/.dat files.
+	#[clap(value_hint=clap::ValueHint::DirPath)]
+	a: PathBuf,
+
+	/// Directory containing the second set of .dir/.dat files.
+	#[clap(value_hint=clap::ValueHint::DirPath)]
+	b: PathBuf,
+}
+
+pub fn run(Command { a, b }: Command) -> Result<(), Report> {
+	let arcs_a = Archives::new(&a)
+		.with_context(|| format!("could not read archives from {}", a.display()))?;
+	let arcs_b = Archives::new(&b)
+		.with_context(|| format!("could not read archives from {}", b.display()))?;
+	print!("{}", Diff::new(&arcs_a, &arcs_b)?);
+	Ok(())
+}
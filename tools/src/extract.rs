@@ -4,6 +4,7 @@ use std::{
 	io::Write as _, ffi::OsStr,
 };
 use indicatif::{ProgressBar, MultiProgress, ProgressStyle};
+use rayon::prelude::*;
 use themelios::archive::{Archive, Archives};
 use eyre::*;
 
@@ -14,6 +15,10 @@ pub struct Command {
 	#[clap(short, long)]
 	force: bool,
 
+	/// Extract archives even if they are malformed, printing what is wrong with them.
+	#[clap(short, long)]
+	lenient: bool,
+
 	/// .dir file or directory to read from.
 	///
 	/// If a file, extract its contents to <outdir>/.
@@ -26,10 +31,10 @@ pub struct Command {
 	outdir: PathBuf,
 }
 
-const BAR_STYLE: &str = "{wide_bar} {msg:>12} ({bytes}/{total_bytes})";
+const BAR_STYLE: &str = "{prefix} {wide_bar} {msg:>12} ({bytes}/{total_bytes})";
 const TOTAL_BAR_STYLE: &str = "{elapsed_precise} ({percent}%) {wide_bar} {msg}";
 
-pub fn run(Command { force, infile, outdir }: Command) -> Result<(), Report> {
+pub fn run(Command { force, lenient, infile, outdir }: Command) -> Result<(), Report> {
 	let meta = infile.metadata()
 		.with_context(|| format!("could stat open {}", infile.display()))?;
 	if meta.is_file() {
@@ -43,21 +48,34 @@ pub fn run(Command { force, infile, outdir }: Command) -> Result<(), Report> {
 		let datfile = infile.with_extension("dat");
 		let dat = File::open(&datfile)
 			.with_context(|| format!("could not open {}", datfile.display()))?;
-		let arc = Archive::from_dir_dat(&dir, &dat)
-			.with_context(|| format!("could not read archive from {}", infile.display()))?;
+		let arc = if lenient {
+			Archive::from_dir_dat_lenient(&dir, &dat).map(|(arc, report)| {
+				eprint!("{report}");
+				arc
+			})
+		} else {
+			Archive::from_dir_dat(&dir, &dat)
+		}.with_context(|| format!("could not read archive from {}", infile.display()))?;
 
 		let bar = ProgressBar::new(0)
-			.with_style(ProgressStyle::with_template(BAR_STYLE).unwrap());
+			.with_style(ProgressStyle::with_template(BAR_STYLE).unwrap())
+			.with_prefix(infile.file_stem().unwrap_or_default().to_string_lossy().into_owned());
 		extract(force, &arc, &outdir, bar, None)?;
 	} else if meta.is_dir() {
-		let arcs = Archives::new(&infile)
-			.with_context(|| format!("could not read archives from {}", infile.display()))?;
-		ensure!(
-			arcs.archives().next().is_some(),
-			"no archives in {}", infile.display(),
-		);
-		let mut arcs = arcs.archives().collect::<Vec<_>>();
-		arcs.sort_by_key(|a| a.0);
+		let arcs = if lenient {
+			Archives::new_lenient(&infile).map(|(arcs, reports)| {
+				for (i, report) in reports {
+					eprint!("ED6_DT{i:02X}:\n{report}");
+				}
+				arcs
+			})
+		} else {
+			Archives::new(&infile)
+		}.with_context(|| format!("could not read archives from {}", infile.display()))?;
+		let arcs = arcs.archives()
+			.with_context(|| format!("could not read archives from {}", infile.display()))?
+			.collect::<Vec<_>>();
+		ensure!(!arcs.is_empty(), "no archives in {}", infile.display());
 
 		let mpb = MultiProgress::new();
 
@@ -66,14 +84,14 @@ pub fn run(Command { force, infile, outdir }: Command) -> Result<(), Report> {
 			.with_style(ProgressStyle::with_template(TOTAL_BAR_STYLE).unwrap());
 		mpb.add(outerbar.clone());
 
-		for (i, arc) in arcs {
-			outerbar.set_message(format!("ED6_DT{i:02X}"));
-
+		// The archives are extracted in parallel too, so that small archives don't leave cores idle
+		arcs.par_iter().try_for_each(|&(i, arc)| {
 			let bar = ProgressBar::new(0)
-				.with_style(ProgressStyle::with_template(BAR_STYLE).unwrap());
+				.with_style(ProgressStyle::with_template(BAR_STYLE).unwrap())
+				.with_prefix(format!("ED6_DT{i:02X}"));
 			mpb.add(bar.clone());
-			extract(force, arc, &outdir.join(format!("{i:02X}")), bar, Some(outerbar.clone()))?;
-		}
+			extract(force, arc, &outdir.join(format!("{i:02X}")), bar, Some(outerbar.clone()))
+		})?;
 	} else {
 		bail!("cannot handle {}", infile.display());
 	}
@@ -95,14 +113,11 @@ fn extract(force: bool, arc: &Archive, outdir: &Path, bar: ProgressBar, outerbar
 	fs::create_dir_all(outdir)
 		.with_context(|| format!("failed to create output directory {}", outdir.display()))?;
 
-	let mut index = fs::File::create(outdir.join("index"))
-		.with_context(|| format!("failed to create index {}", outdir.join("index").display()))?;
-
 	bar.set_length(arc.entries().iter().map(|e| e.len() as u64).sum());
-	for e in arc.entries() {
+	let lines = arc.entries().par_iter().map_init(Vec::new, |buf: &mut Vec<u8>, e| -> Result<Option<String>, Error> {
 		bar.set_message(e.name.to_owned());
 		let (rawlen, outlen) = if &e.name == "/_______.___" {
-			continue
+			return Ok(None)
 		} else if e.timestamp == 0 {
 			(0, None)
 		} else {
@@ -114,7 +129,8 @@ fn extract(force: bool, arc: &Archive, outdir: &Path, bar: ProgressBar, outerbar
 			filetime::set_file_mtime(&outfile, filetime::FileTime::from_unix_time(e.timestamp as i64, 0))
 				.with_context(|| format!("failed to set mtime on {}", outfile.display()))?;
 
-			let decomp = themelios::decompress::decompress(raw).ok();
+			buf.clear();
+			let decomp = themelios::decompress::decompress_into(raw, buf).is_ok().then_some(&*buf);
 			if let Some(decomp) = &decomp {
 				let outfile2 = outdir.join(format!("{}.dec", e.name));
 				fs::write(&outfile2, decomp)
@@ -132,17 +148,25 @@ fn extract(force: bool, arc: &Archive, outdir: &Path, bar: ProgressBar, outerbar
 			format!("{}", rawlen)
 		};
 
-		writeln!(index, "{:4} {:12} {ts1} {ts2} ({lenstr}; {} {})",
+		let line = format!("{:4} {:12} {ts1} {ts2} ({lenstr}; {} {})",
 			e.index, e.name,
 			e.unk1, e.unk2,
 			ts1 = chrono::NaiveDateTime::from_timestamp(e.timestamp as i64, 0),
 			ts2 = chrono::NaiveDateTime::from_timestamp(e.unk3 as i64, 0),
-		).context("failed to write to index")?;
+		);
 
 		bar.inc(e.len() as u64);
 		if let Some(outerbar) = &outerbar {
 			outerbar.inc(e.len() as u64);
 		}
+		Ok(Some(line))
+	}).collect::<Result<Vec<_>, _>>()?;
+
+	// Written afterwards, so that the index is in order regardless of which thread finished first
+	let mut index = fs::File::create(outdir.join("index"))
+		.with_context(|| format!("failed to create index {}", outdir.join("index").display()))?;
+	for line in lines.into_iter().flatten() {
+		writeln!(index, "{line}").context("failed to write to index")?;
 	}
 
 	Ok(())
@@ -2,6 +2,10 @@ use clap::StructOpt;
 
 mod extract;
 mod decompress;
+mod compress;
+mod diff;
+mod detect;
+mod po;
 
 #[derive(Debug, Clone, clap::Parser)]
 struct Cli {
@@ -13,6 +17,10 @@ struct Cli {
 enum Command {
 	Extract(extract::Command),
 	Decompress(decompress::Command),
+	Compress(compress::Command),
+	Diff(diff::Command),
+	Detect(detect::Command),
+	Po(po::Command),
 }
 
 fn main() -> Result<(), eyre::Report> {
@@ -27,6 +35,10 @@ fn main() -> Result<(), eyre::Report> {
 	match cli.command {
 		Command::Extract(command) => extract::run(command)?,
 		Command::Decompress(command) => decompress::run(command)?,
+		Command::Compress(command) => compress::run(command)?,
+		Command::Diff(command) => diff::run(command)?,
+		Command::Detect(command) => detect::run(command)?,
+		Command::Po(command) => po::run(command)?,
 	}
 	Ok(())
 }
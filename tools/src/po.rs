@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use themelios::gamedata::{GameData, install::Install};
+use themelios::util::Encoding;
+use themelios::scena::{ed6, ed7, translate};
+use eyre::*;
+
+/// Export the dialogue of scena files to a PO file for translation, or import it back
+#[derive(Debug, Clone, clap::Args)]
+pub struct Command {
+	#[clap(subcommand)]
+	command: Sub,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum Sub {
+	/// Write all text, menus and npc names to a PO file
+	Export {
+		/// The game's install directory
+		#[clap(value_hint=clap::ValueHint::DirPath)]
+		game: PathBuf,
+		/// The game's text encoding, for installs where it cannot be detected (cp932 or gbk)
+		#[clap(short, long, value_parser=parse_encoding)]
+		encoding: Option<Encoding>,
+		/// Where to write the PO file
+		#[clap(short, long, value_hint=clap::ValueHint::FilePath)]
+		output: PathBuf,
+		/// Uncompressed scena files
+		#[clap(value_hint=clap::ValueHint::FilePath, required=true)]
+		files: Vec<PathBuf>,
+	},
+	/// Write the translations in a PO file into scena files
+	Import {
+		/// The game's install directory
+		#[clap(value_hint=clap::ValueHint::DirPath)]
+		game: PathBuf,
+		/// The game's text encoding, for installs where it cannot be detected (cp932 or gbk)
+		#[clap(short, long, value_parser=parse_encoding)]
+		encoding: Option<Encoding>,
+		#[clap(value_hint=clap::ValueHint::FilePath)]
+		po: PathBuf,
+		/// Directory to write the translated files to
+		#[clap(short, long, value_hint=clap::ValueHint::DirPath)]
+		output: PathBuf,
+		/// Uncompressed scena files
+		#[clap(value_hint=clap::ValueHint::FilePath, required=true)]
+		files: Vec<PathBuf>,
+	},
+}
+
+pub fn run(Command { command }: Command) -> Result<(), Report> {
+	match command {
+		Sub::Export { game, encoding, output, files } => export(&game_data(&game, encoding)?, &output, &files),
+		Sub::Import { game, encoding, po, output, files } => import(&game_data(&game, encoding)?, &po, &output, &files),
+	}
+}
+
+fn parse_encoding(s: &str) -> Result<Encoding, String> {
+	match s {
+		"cp932" | "sjis" => Ok(Encoding::Cp932),
+		"gbk" => Ok(Encoding::Gbk),
+		_ => Err(format!("unknown encoding {s:?}")),
+	}
+}
+
+fn game_data(path: &Path, encoding: Option<Encoding>) -> Result<GameData<'static>, Report> {
+	let mut install = Install::detect(path)?;
+	if encoding.is_some() {
+		install.encoding = encoding;
+	}
+	Ok(install.game_data()?)
+}
+
+/// The file name without extension, which is what ids are based on.
+fn file_id(path: &Path) -> Result<String, Report> {
+	let name = path.file_name().and_then(|a| a.to_str()).ok_or_else(|| eyre!("invalid file name {}", path.display()))?;
+	Ok(name.split('.').next().unwrap_or(name).to_lowercase())
+}
+
+fn export(game: &GameData, output: &Path, files: &[PathBuf]) -> Result<(), Report> {
+	let mut out = String::new();
+	writeln!(out, "msgid \"\"")?;
+	writeln!(out, "msgstr \"\"")?;
+	writeln!(out, "\"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+	for path in files {
+		let id = file_id(path)?;
+		let data = std::fs::read(path)?;
+		let entries = if game.iset.is_ed7() {
+			translate::extract_ed7(game, &id, &ed7::read(game, &data).with_context(|| path.display().to_string())?)
+		} else {
+			translate::extract_ed6(game, &id, &ed6::read(game, &data).with_context(|| path.display().to_string())?)
+		};
+		for e in entries {
+			writeln!(out)?;
+			if let Some(speaker) = &e.speaker {
+				writeln!(out, "#. Speaker: {speaker}")?;
+			}
+			writeln!(out, "#. {}", e.context)?;
+			writeln!(out, "msgctxt {}", quote(&e.id))?;
+			writeln!(out, "msgid {}", quote(&e.text))?;
+			writeln!(out, "msgstr \"\"")?;
+		}
+	}
+	std::fs::write(output, out)?;
+	Ok(())
+}
+
+fn import(game: &GameData, po: &Path, output: &Path, files: &[PathBuf]) -> Result<(), Report> {
+	let translations = parse(&std::fs::read_to_string(po)?).with_context(|| po.display().to_string())?;
+	std::fs::create_dir_all(output)?;
+	let mut failed = 0;
+	for path in files {
+		let id = file_id(path)?;
+		let data = std::fs::read(path)?;
+		let (data, errors) = if game.iset.is_ed7() {
+			let mut scena = ed7::read(game, &data).with_context(|| path.display().to_string())?;
+			let errors = translate::apply_ed7(game, &id, &mut scena, &translations);
+			(ed7::write(game, &scena)?, errors)
+		} else {
+			let mut scena = ed6::read(game, &data).with_context(|| path.display().to_string())?;
+			let errors = translate::apply_ed6(game, &id, &mut scena, &translations);
+			(ed6::write(game, &scena)?, errors)
+		};
+		for e in &errors {
+			eprintln!("{e}");
+		}
+		failed += errors.len();
+		std::fs::write(output.join(path.file_name().unwrap()), data)?;
+	}
+	if failed > 0 {
+		bail!("{failed} strings could not be imported");
+	}
+	Ok(())
+}
+
+fn quote(s: &str) -> String {
+	let escape = |s: &str| {
+		let mut out = String::from("\"");
+		for c in s.chars() {
+			match c {
+				'\\' => out.push_str("\\\\"),
+				'"' => out.push_str("\\\""),
+				'\n' => out.push_str("\\n"),
+				'\t' => out.push_str("\\t"),
+				c => out.push(c),
+			}
+		}
+		out.push('"');
+		out
+	};
+	if s.trim_end_matches('\n').contains('\n') {
+		let mut out = String::from("\"\"");
+		for line in s.split_inclusive('\n') {
+			out.push('\n');
+			out.push_str(&escape(line));
+		}
+		out
+	} else {
+		escape(s)
+	}
+}
+
+fn unquote(s: &str) -> Option<String> {
+	let s = s.trim().strip_prefix('"')?.strip_suffix('"')?;
+	let mut out = String::new();
+	let mut chars = s.chars();
+	while let Some(c) = chars.next() {
+		out.push(match c {
+			'\\' => match chars.next()? {
+				'\\' => '\\',
+				'"' => '"',
+				'n' => '\n',
+				't' => '\t',
+				_ => return None,
+			},
+			'"' => return None,
+			c => c,
+		});
+	}
+	Some(out)
+}
+
+/// Reads the translated, non-fuzzy entries of a PO file, keyed by msgctxt.
+fn parse(text: &str) -> Result<BTreeMap<String, String>, Report> {
+	#[derive(Default)]
+	struct Entry {
+		fuzzy: bool,
+		ctxt: Option<String>,
+		str: String,
+	}
+
+	let mut out = BTreeMap::new();
+	let mut add = |e: Entry| {
+		if let Some(ctxt) = e.ctxt {
+			if !e.fuzzy && !e.str.is_empty() {
+				out.insert(ctxt, e.str);
+			}
+		}
+	};
+
+	let mut entry = Entry::default();
+	let mut field = None;
+	for (n, line) in text.lines().enumerate() {
+		let line = line.trim();
+		let bad = || eyre!("invalid line {}: {line}", n + 1);
+		if line.is_empty() {
+			continue
+		}
+		if field == Some("msgstr") && !line.starts_with('"') {
+			add(std::mem::take(&mut entry));
+			field = None;
+		}
+		if let Some(flags) = line.strip_prefix("#,") {
+			entry.fuzzy |= flags.split(',').any(|a| a.trim() == "fuzzy");
+			continue
+		}
+		if line.starts_with('#') {
+			continue
+		}
+		let (kw, rest) = match line.split_once(' ') {
+			Some((kw, rest)) if !line.starts_with('"') => (Some(kw), rest),
+			_ => (None, line),
+		};
+		let s = unquote(rest).ok_or_else(bad)?;
+		field = match kw {
+			Some("msgctxt") => { entry.ctxt = Some(s); kw }
+			Some("msgid") => kw,
+			Some("msgstr") => { entry.str = s; kw }
+			None => {
+				match field {
+					Some("msgctxt") => entry.ctxt.get_or_insert_with(String::new).push_str(&s),
+					Some("msgid") => {}
+					Some("msgstr") => entry.str.push_str(&s),
+					_ => return Err(bad()),
+				}
+				field
+			}
+			Some(_) => return Err(bad()),
+		};
+	}
+	add(entry);
+	Ok(out)
+}